@@ -0,0 +1,29 @@
+use std::path::Path;
+
+/// Keeps the music playing for as long as it is alive.
+#[cfg(feature = "audio")]
+pub type Music = rodio::OutputStream;
+#[cfg(not(feature = "audio"))]
+pub type Music = ();
+
+// The music is optional, the returned stream has to be kept alive for it to
+// keep playing.
+#[cfg(feature = "audio")]
+pub fn play_music(data_dir: &Path) -> Option<Music> {
+    use rodio::{source::Source, Decoder, OutputStream};
+    use std::fs::File;
+    use std::io::BufReader;
+
+    let (stream, stream_handle) = OutputStream::try_default().ok()?;
+    let file = File::open(data_dir.join("background-music.ogg")).ok()?;
+    let source = Decoder::new(BufReader::new(file)).ok()?;
+    stream_handle.play_raw(source.convert_samples()).ok()?;
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    Some(stream)
+}
+
+// Built without sound, there is never any music.
+#[cfg(not(feature = "audio"))]
+pub fn play_music(_data_dir: &Path) -> Option<Music> {
+    None
+}
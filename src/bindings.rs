@@ -0,0 +1,438 @@
+use minifb::{Key, MouseButton};
+use std::error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use toml_edit::{Array, DocumentMut, Item, Table, Value};
+
+pub const CONFIG_FILE: &str = "controls.toml";
+const TABLE: &str = "bindings";
+
+/// Something the player can do, bound to any number of keys and buttons.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    Forward,
+    Back,
+    TurnLeft,
+    TurnRight,
+    /// Held to make the turn actions strafe instead.
+    Strafe,
+    StrafeLeft,
+    StrafeRight,
+    Run,
+    Use,
+    Fire,
+    Weapon1,
+    Weapon2,
+    Weapon3,
+    Weapon4,
+    Map,
+    Pause,
+    Screenshot,
+    Rebind,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 19] = [
+        Action::Forward,
+        Action::Back,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::Strafe,
+        Action::StrafeLeft,
+        Action::StrafeRight,
+        Action::Run,
+        Action::Use,
+        Action::Fire,
+        Action::Weapon1,
+        Action::Weapon2,
+        Action::Weapon3,
+        Action::Weapon4,
+        Action::Map,
+        Action::Pause,
+        Action::Screenshot,
+        Action::Rebind,
+        Action::Quit,
+    ];
+
+    /// The action's key in the config file.
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Forward => "forward",
+            Action::Back => "back",
+            Action::TurnLeft => "turn_left",
+            Action::TurnRight => "turn_right",
+            Action::Strafe => "strafe",
+            Action::StrafeLeft => "strafe_left",
+            Action::StrafeRight => "strafe_right",
+            Action::Run => "run",
+            Action::Use => "use",
+            Action::Fire => "fire",
+            Action::Weapon1 => "weapon_1",
+            Action::Weapon2 => "weapon_2",
+            Action::Weapon3 => "weapon_3",
+            Action::Weapon4 => "weapon_4",
+            Action::Map => "map",
+            Action::Pause => "pause",
+            Action::Screenshot => "screenshot",
+            Action::Rebind => "rebind",
+            Action::Quit => "quit",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Action> {
+        Action::ALL.into_iter().find(|action| action.name() == name)
+    }
+
+    fn defaults(self) -> Vec<Input> {
+        use Input::{Key as K, Mouse as M};
+        match self {
+            Action::Forward => vec![K(Key::Up), K(Key::W), M(MouseButton::Right)],
+            Action::Back => vec![K(Key::Down), K(Key::S)],
+            Action::TurnLeft => vec![K(Key::Left), K(Key::A)],
+            Action::TurnRight => vec![K(Key::Right), K(Key::D)],
+            Action::Strafe => vec![K(Key::X)],
+            Action::StrafeLeft => vec![K(Key::Q)],
+            Action::StrafeRight => vec![K(Key::E)],
+            Action::Run => vec![K(Key::LeftShift)],
+            Action::Use => vec![K(Key::Space), M(MouseButton::Middle)],
+            Action::Fire => vec![K(Key::LeftCtrl), M(MouseButton::Left)],
+            Action::Weapon1 => vec![K(Key::Key1)],
+            Action::Weapon2 => vec![K(Key::Key2)],
+            Action::Weapon3 => vec![K(Key::Key3)],
+            Action::Weapon4 => vec![K(Key::Key4)],
+            Action::Map => vec![K(Key::Tab)],
+            Action::Pause => vec![K(Key::Pause), K(Key::P)],
+            Action::Screenshot => vec![K(Key::F12)],
+            Action::Rebind => vec![K(Key::F10)],
+            Action::Quit => vec![K(Key::Escape)],
+        }
+    }
+}
+
+/// A key or mouse button.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Input {
+    Key(Key),
+    Mouse(MouseButton),
+}
+
+const MOUSE_BUTTONS: [(MouseButton, &str); 3] = [
+    (MouseButton::Left, "MouseLeft"),
+    (MouseButton::Middle, "MouseMiddle"),
+    (MouseButton::Right, "MouseRight"),
+];
+
+// Every key minifb knows, named like the `Key` variants.
+#[rustfmt::skip]
+const KEYS: [Key; 106] = [
+    Key::Key0, Key::Key1, Key::Key2, Key::Key3, Key::Key4, Key::Key5, Key::Key6, Key::Key7,
+    Key::Key8, Key::Key9, Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I,
+    Key::J, Key::K, Key::L, Key::M, Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U,
+    Key::V, Key::W, Key::X, Key::Y, Key::Z, Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6,
+    Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12, Key::F13, Key::F14, Key::F15,
+    Key::Down, Key::Left, Key::Right, Key::Up, Key::Apostrophe, Key::Backquote, Key::Backslash,
+    Key::Comma, Key::Equal, Key::LeftBracket, Key::Minus, Key::Period, Key::RightBracket,
+    Key::Semicolon, Key::Slash, Key::Backspace, Key::Delete, Key::End, Key::Enter, Key::Escape,
+    Key::Home, Key::Insert, Key::Menu, Key::PageDown, Key::PageUp, Key::Pause, Key::Space,
+    Key::Tab, Key::NumLock, Key::CapsLock, Key::ScrollLock, Key::LeftShift, Key::RightShift,
+    Key::LeftCtrl, Key::RightCtrl, Key::NumPad0, Key::NumPad1, Key::NumPad2, Key::NumPad3,
+    Key::NumPad4, Key::NumPad5, Key::NumPad6, Key::NumPad7, Key::NumPad8, Key::NumPad9,
+    Key::NumPadDot, Key::NumPadSlash, Key::NumPadAsterisk, Key::NumPadMinus, Key::NumPadPlus,
+    Key::NumPadEnter, Key::LeftAlt, Key::RightAlt, Key::LeftSuper, Key::RightSuper,
+];
+
+impl Input {
+    pub fn name(self) -> String {
+        match self {
+            Input::Key(key) => format!("{:?}", key),
+            Input::Mouse(button) => MOUSE_BUTTONS
+                .iter()
+                .find(|(known, _)| *known == button)
+                .map_or_else(|| format!("{:?}", button), |(_, name)| name.to_string()),
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Input> {
+        KEYS.into_iter()
+            .map(Input::Key)
+            .chain(
+                MOUSE_BUTTONS
+                    .iter()
+                    .map(|(button, _)| Input::Mouse(*button)),
+            )
+            .find(|input| input.name().eq_ignore_ascii_case(name))
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Which keys and buttons trigger each action. Actions the config file does
+/// not mention keep their defaults.
+#[derive(Debug, Clone)]
+pub struct Bindings {
+    inputs: Vec<Vec<Input>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            inputs: Action::ALL.iter().map(|action| action.defaults()).collect(),
+        }
+    }
+}
+
+impl Bindings {
+    /// Reads the bindings from a config file, a missing file gives the
+    /// defaults.
+    pub fn load(file: &Path) -> Result<Self, BindingsError> {
+        match fs::read_to_string(file) {
+            Ok(text) => Self::parse(file, &text),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(source) => Err(BindingsError::Read {
+                file: file.to_owned(),
+                source,
+            }),
+        }
+    }
+
+    /// Parses a `[bindings]` table mapping action names to a key or a list
+    /// of keys, e.g. `forward = ["Up", "Z", "MouseRight"]`.
+    pub fn parse(file: &Path, text: &str) -> Result<Self, BindingsError> {
+        let syntax = |message: String| BindingsError::Syntax {
+            file: file.to_owned(),
+            message,
+        };
+        let document: DocumentMut = text.parse().map_err(|err| syntax(format!("{}", err)))?;
+        let mut bindings = Self::default();
+        let table = match document.get(TABLE) {
+            Some(item) => item
+                .as_table_like()
+                .ok_or_else(|| syntax(format!("`{}` is not a table", TABLE)))?,
+            None => return Ok(bindings),
+        };
+
+        for (name, item) in table.iter() {
+            let action = Action::from_name(name).ok_or_else(|| BindingsError::UnknownAction {
+                file: file.to_owned(),
+                action: name.to_owned(),
+            })?;
+            let names: Vec<&str> = match item.as_value() {
+                Some(Value::String(name)) => vec![name.value().as_str()],
+                Some(Value::Array(names)) => names
+                    .iter()
+                    .map(|name| name.as_str())
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| syntax(format!("`{}` must list key names", action.name())))?,
+                _ => return Err(syntax(format!("`{}` must list key names", action.name()))),
+            };
+            let inputs = names
+                .into_iter()
+                .map(|name| {
+                    Input::from_name(name).ok_or_else(|| BindingsError::UnknownInput {
+                        file: file.to_owned(),
+                        action: action.name().to_owned(),
+                        input: name.to_owned(),
+                    })
+                })
+                .collect::<Result<_, _>>()?;
+            bindings.set(action, inputs);
+        }
+        Ok(bindings)
+    }
+
+    /// Writes every binding to the config file, keeping whatever else it
+    /// holds. A file that no longer parses is left alone.
+    pub fn save(&self, file: &Path) -> Result<(), BindingsError> {
+        let syntax = |message: String| BindingsError::Syntax {
+            file: file.to_owned(),
+            message,
+        };
+        let mut document: DocumentMut = match fs::read_to_string(file) {
+            Ok(text) => text.parse().map_err(|err| syntax(format!("{}", err)))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => DocumentMut::new(),
+            Err(source) => {
+                return Err(BindingsError::Read {
+                    file: file.to_owned(),
+                    source,
+                })
+            }
+        };
+        if !document.contains_key(TABLE) {
+            document.insert(TABLE, Item::Table(Table::new()));
+        }
+        let table = document[TABLE]
+            .as_table_like_mut()
+            .ok_or_else(|| syntax(format!("`{}` is not a table", TABLE)))?;
+        for action in Action::ALL {
+            let names: Array = self.get(action).iter().map(|input| input.name()).collect();
+            table.insert(action.name(), Item::Value(Value::Array(names)));
+        }
+        fs::write(file, document.to_string()).map_err(|source| BindingsError::Write {
+            file: file.to_owned(),
+            source,
+        })
+    }
+
+    pub fn get(&self, action: Action) -> &[Input] {
+        &self.inputs[action as usize]
+    }
+
+    pub fn set(&mut self, action: Action, inputs: Vec<Input>) {
+        self.inputs[action as usize] = inputs;
+    }
+
+    /// Binds one more input to an action, keeping the ones it has.
+    pub fn add(&mut self, action: Action, input: Input) {
+        let inputs = &mut self.inputs[action as usize];
+        if !inputs.contains(&input) {
+            inputs.push(input);
+        }
+    }
+
+    /// Gives an action back its default inputs.
+    pub fn reset(&mut self, action: Action) {
+        self.set(action, action.defaults());
+    }
+}
+
+#[derive(Debug)]
+pub enum BindingsError {
+    Read {
+        file: PathBuf,
+        source: io::Error,
+    },
+    Write {
+        file: PathBuf,
+        source: io::Error,
+    },
+    Syntax {
+        file: PathBuf,
+        message: String,
+    },
+    UnknownAction {
+        file: PathBuf,
+        action: String,
+    },
+    UnknownInput {
+        file: PathBuf,
+        action: String,
+        input: String,
+    },
+}
+
+impl fmt::Display for BindingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BindingsError::Read { file, source } => {
+                write!(f, "{}: could not read file: {}", file.display(), source)
+            }
+            BindingsError::Write { file, source } => {
+                write!(f, "{}: could not write file: {}", file.display(), source)
+            }
+            BindingsError::Syntax { file, message } => {
+                write!(f, "{}: {}", file.display(), message.trim_end())
+            }
+            BindingsError::UnknownAction { file, action } => {
+                write!(f, "{}: unknown action `{}`", file.display(), action)
+            }
+            BindingsError::UnknownInput {
+                file,
+                action,
+                input,
+            } => write!(
+                f,
+                "{}: unknown key `{}` for `{}`",
+                file.display(),
+                input,
+                action
+            ),
+        }
+    }
+}
+
+impl error::Error for BindingsError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            BindingsError::Read { source, .. } | BindingsError::Write { source, .. } => {
+                Some(source)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn config_overrides_some_actions_and_keeps_the_rest() {
+        let text = "[bindings]\nforward = [\"Z\", \"up\", \"MouseRight\"]\nstrafe_left = \"A\"\n";
+        let bindings = Bindings::parse(Path::new("controls.toml"), text).unwrap();
+        assert_eq!(
+            [
+                Input::Key(Key::Z),
+                Input::Key(Key::Up),
+                Input::Mouse(MouseButton::Right)
+            ],
+            bindings.get(Action::Forward)
+        );
+        assert_eq!([Input::Key(Key::A)], bindings.get(Action::StrafeLeft));
+        assert_eq!(Action::Back.defaults(), bindings.get(Action::Back));
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        let file = Path::new("controls.toml");
+        assert!(matches!(
+            Bindings::parse(file, "[bindings]\njump = \"Space\""),
+            Err(BindingsError::UnknownAction { .. })
+        ));
+        assert!(matches!(
+            Bindings::parse(file, "[bindings]\nfire = [\"Trigger\"]"),
+            Err(BindingsError::UnknownInput { .. })
+        ));
+    }
+
+    #[test]
+    fn saved_bindings_load_back() {
+        let file = std::env::temp_dir().join(format!(
+            "rust-raycasting-controls-{}.toml",
+            std::process::id()
+        ));
+        fs::write(&file, "# my layout\n[bindings]\nforward = \"Z\"\n").unwrap();
+        let mut bindings = Bindings::load(&file).unwrap();
+        bindings.set(Action::Fire, vec![Input::Key(Key::RightCtrl)]);
+        bindings.save(&file).unwrap();
+
+        let text = fs::read_to_string(&file).unwrap();
+        let loaded = Bindings::load(&file).unwrap();
+        fs::remove_file(&file).unwrap();
+        assert!(text.starts_with("# my layout"));
+        for action in Action::ALL {
+            assert_eq!(bindings.get(action), loaded.get(action));
+        }
+    }
+
+    #[test]
+    fn saving_leaves_a_broken_file_alone() {
+        let file = std::env::temp_dir().join(format!(
+            "rust-raycasting-broken-controls-{}.toml",
+            std::process::id()
+        ));
+        let text = "[bindings]\nforward = [\"Z\"\n";
+        fs::write(&file, text).unwrap();
+        let saved = Bindings::default().save(&file);
+        let left = fs::read_to_string(&file).unwrap();
+        fs::remove_file(&file).unwrap();
+        assert!(matches!(saved, Err(BindingsError::Syntax { .. })));
+        assert_eq!(text, left);
+    }
+}
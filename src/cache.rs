@@ -1,7 +1,11 @@
 use crate::constants::{MAP_HEIGHT, MAP_WIDTH};
 use crate::map::Map;
+use std::cell::OnceCell;
+use std::error;
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
 
 pub const H_BJPIC: usize = 3;
 pub const H_CASTLEPIC: usize = 4;
@@ -152,56 +156,248 @@ pub const NUMCHUNKS: u32 = 161;
 pub const NUMPICS: usize = 144;
 pub const STARTPICS: usize = 3;
 
-const NUM_MAPS: usize = 60;
+pub const NUM_EPISODES: usize = 6;
+pub const LEVELS_PER_EPISODE: usize = 10;
+const NUM_MAPS: usize = NUM_EPISODES * LEVELS_PER_EPISODE;
+const WL6_NUMPICS: usize = 132;
+const SOD_NUMPICS: usize = 147;
+const SDM_NUMPICS: usize = 142;
+const HUFF_NODES: usize = 256;
+const MAP_LEVEL_HEADER_LEN: usize = 38;
+const RLEW_TAG: [u8; 2] = [0xCD, 0xAB];
+const GAMEMAPS_MAGIC: &[u8] = b"TED5";
+
+pub const DATADIR: &str = "data";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameVersion {
+    Shareware,
+    Registered,
+    SpearOfDestiny,
+    SpearDemo,
+}
+
+impl GameVersion {
+    const ALL: [GameVersion; 4] = [
+        GameVersion::Registered,
+        GameVersion::Shareware,
+        GameVersion::SpearOfDestiny,
+        GameVersion::SpearDemo,
+    ];
+
+    pub fn from_extension(extension: &str) -> Option<GameVersion> {
+        GameVersion::ALL
+            .into_iter()
+            .find(|version| version.extension().eq_ignore_ascii_case(extension))
+    }
+
+    pub fn detect<P: AsRef<Path>>(data_dir: P) -> Result<GameVersion, CacheError> {
+        let data_dir = data_dir.as_ref();
+        GameVersion::ALL
+            .into_iter()
+            .find(|version| version.data_file(data_dir, "VSWAP").is_file())
+            .ok_or_else(|| CacheError::UnknownVersion {
+                file: data_dir.to_owned(),
+            })
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            GameVersion::Shareware => "WL1",
+            GameVersion::Registered => "WL6",
+            GameVersion::SpearOfDestiny => "SOD",
+            GameVersion::SpearDemo => "SDM",
+        }
+    }
+
+    pub fn data_file(self, data_dir: &Path, name: &str) -> PathBuf {
+        data_dir.join(format!("{}.{}", name, self.extension()))
+    }
+
+    pub fn num_pics(self) -> usize {
+        match self {
+            GameVersion::Shareware => NUMPICS,
+            GameVersion::Registered => WL6_NUMPICS,
+            GameVersion::SpearOfDestiny => SOD_NUMPICS,
+            GameVersion::SpearDemo => SDM_NUMPICS,
+        }
+    }
+
+    pub fn start_pics(self) -> usize {
+        STARTPICS
+    }
+
+    // Picture constants in this module use the shareware chunk numbers, this
+    // maps them to the chunk holding the same picture in this version's
+    // VGAGRAPH, or None if the version does not have it.
+    pub fn pic_chunk(self, pic: usize) -> Option<usize> {
+        match self {
+            GameVersion::Shareware => Some(pic),
+            GameVersion::Registered => match pic {
+                H_BJPIC | H_CASTLEPIC => Some(pic),
+                H_BLAZEPIC => Some(5),
+                H_TOPWINDOWPIC..=H_BOTTOMINFOPIC => Some(pic - 11),
+                C_OPTIONSPIC..=GETPSYCHEDPIC => Some(pic - 12),
+                _ => None,
+            },
+            GameVersion::SpearOfDestiny => spear_pic_chunk(pic),
+            GameVersion::SpearDemo => match spear_pic_chunk(pic)? {
+                chunk if chunk >= SOD_L_GUYPIC => Some(chunk - 5),
+                chunk => Some(chunk),
+            },
+        }
+    }
+}
 
-const DATADIR: &str = "data";
+const SOD_L_GUYPIC: usize = 36;
+
+fn spear_pic_chunk(pic: usize) -> Option<usize> {
+    match pic {
+        C_MOUSELBACKPIC => Some(4),
+        C_CURSOR1PIC..=C_SELECTEDPIC => Some(pic - 18),
+        C_CUSTOMIZEPIC => Some(9),
+        C_JOY1PIC | C_JOY2PIC => Some(pic - 43),
+        C_CONTROLPIC => Some(15),
+        C_OPTIONSPIC => Some(16),
+        C_FXTITLEPIC..=C_MUSICTITLEPIC => Some(pic - 10),
+        C_BABYMODEPIC..=C_HARDPIC => Some(pic - 10),
+        C_DISKLOADING1PIC | C_DISKLOADING2PIC => Some(pic - 11),
+        C_LOADGAMEPIC | C_SAVEGAMEPIC => Some(pic - 13),
+        HIGHSCORESPIC => Some(29),
+        L_GUYPIC..=L_BJWINSPIC => Some(pic - L_GUYPIC + SOD_L_GUYPIC),
+        TITLEPIC => Some(79),
+        STATUSBARPIC => Some(81),
+        PG13PIC | CREDITSPIC => Some(pic - 18),
+        KNIFEPIC..=GOTGATLINGPIC => Some(pic - 19),
+        PAUSEDPIC | GETPSYCHEDPIC => Some(pic - 14),
+        _ => None,
+    }
+}
 
 pub struct Cache {
-    pics: Vec<Picture>,
-    textures: Vec<Vec<u8>>,
-    sprites: Vec<(CompShape, Vec<u8>)>,
-    sounds: Vec<Vec<u8>>,
-    maps: Vec<Map>,
+    version: GameVersion,
+    huff: Vec<(u16, u16)>,
+    graph: DataFile,
+    graph_headers: DataFile,
+    pictable: Vec<(u16, u16)>,
+    pics: Vec<OnceCell<Picture>>,
+    vswap: DataFile,
+    pages: Vec<(usize, usize)>,
+    sprite_start: usize,
+    sound_start: usize,
+    sprites: Vec<OnceCell<(CompShape, Vec<u8>)>>,
+    map_head: MapHead,
+    game_maps: DataFile,
+    maps: Vec<OnceCell<Map>>,
 }
 
 impl Cache {
-    pub fn new(
-        pics: Vec<Picture>,
-        textures: Vec<Vec<u8>>,
-        sprites: Vec<(CompShape, Vec<u8>)>,
-        sounds: Vec<Vec<u8>>,
-        maps: Vec<Map>,
-    ) -> Cache {
-        Cache {
-            pics,
-            textures,
-            sprites,
-            sounds,
-            maps,
+    pub fn version(&self) -> GameVersion {
+        self.version
+    }
+
+    pub fn get_pic(&self, index: usize) -> Result<&Picture, CacheError> {
+        let start_pics = self.version.start_pics();
+        let slot = self
+            .version
+            .pic_chunk(index)
+            .and_then(|chunk| chunk.checked_sub(start_pics))
+            .filter(|&slot| slot < self.pics.len())
+            .ok_or_else(|| self.graph.missing_chunk(index))?;
+
+        memoize(&self.pics[slot], || {
+            let (width, height) = self.pictable[slot];
+            let data = load_graphic(
+                self.graph.chunk(),
+                self.graph_headers.chunk(),
+                &self.huff,
+                slot + start_pics,
+            )?;
+            Ok(Picture {
+                width: width as u32,
+                height: height as u32,
+                data,
+            })
+        })
+    }
+
+    pub fn get_texture(&self, index: usize) -> Result<&[u8], CacheError> {
+        if index >= self.sprite_start {
+            return Err(self.vswap.missing_chunk(index));
         }
+        Ok(self.page(index)?.bytes)
     }
 
-    pub fn get_pic(&self, index: usize) -> &Picture {
-        &self.pics[index - 3]
+    pub fn door_wall(&self) -> usize {
+        self.sprite_start.saturating_sub(8)
     }
 
-    pub fn get_texture(&self, index: usize) -> &Vec<u8> {
-        &self.textures[index]
+    pub fn get_sprite(&self, index: usize) -> Result<&(CompShape, Vec<u8>), CacheError> {
+        let page = self.sprite_start + index;
+        let cell = self
+            .sprites
+            .get(index)
+            .ok_or_else(|| self.vswap.missing_chunk(page))?;
+
+        memoize(cell, || {
+            let value = self.page(page)?;
+            let left_pix = value.u16_at(0)?;
+            let right_pix = value.u16_at(2)?;
+            let mut dataofs = Vec::new();
+            for i in 0..=right_pix.saturating_sub(left_pix) as usize {
+                dataofs.push(value.u16_at(4 + i * 2)?);
+            }
+            Ok((
+                CompShape {
+                    left_pix,
+                    right_pix,
+                    dataofs,
+                },
+                value.bytes.to_vec(),
+            ))
+        })
     }
 
-    pub fn get_sprite(&self, index: usize) -> &(CompShape, Vec<u8>) {
-        &self.sprites[index]
+    pub fn get_sound(&self, index: usize) -> Result<&[u8], CacheError> {
+        Ok(self.page(self.sound_start + index)?.bytes)
     }
 
-    pub fn get_sound(&self, index: usize) -> &Vec<u8> {
-        &self.sounds[index]
+    pub fn get_map(&self, episode: usize, level: usize) -> Result<&Map, CacheError> {
+        let index = level + LEVELS_PER_EPISODE * episode;
+        let missing_map = || CacheError::MissingMap {
+            file: self.game_maps.path.clone(),
+            episode,
+            level,
+        };
+        let cell = match self.maps.get(index) {
+            Some(cell) if level < LEVELS_PER_EPISODE => cell,
+            _ => return Err(missing_map()),
+        };
+
+        memoize(cell, || {
+            parse_map(self.game_maps.chunk(), &self.map_head, index)?.ok_or_else(missing_map)
+        })
     }
 
-    pub fn get_map(&self, episode: usize, level: usize) -> Map {
-        self.maps[level + 10 * episode].clone()
+    fn page(&self, page: usize) -> Result<Chunk<'_>, CacheError> {
+        match self.pages.get(page) {
+            Some(&(start, end)) if end > start => self.vswap.chunk().slice(start, end),
+            _ => Err(self.vswap.missing_chunk(page)),
+        }
     }
 }
 
+fn memoize<T>(
+    cell: &OnceCell<T>,
+    load: impl FnOnce() -> Result<T, CacheError>,
+) -> Result<&T, CacheError> {
+    if let Some(value) = cell.get() {
+        return Ok(value);
+    }
+    let value = load()?;
+    Ok(cell.get_or_init(|| value))
+}
+
 pub struct Picture {
     pub width: u32,
     pub height: u32,
@@ -215,116 +411,347 @@ pub struct CompShape {
     pub dataofs: Vec<u16>,
 }
 
-pub fn init() -> Cache {
-    let huff_file = fs::read(DATADIR.to_owned() + "/VGADICT.WL1")
-        .expect("Something went wrong reading the file");
+#[derive(Debug)]
+pub enum CacheError {
+    MissingFile {
+        file: PathBuf,
+        source: io::Error,
+    },
+    BadMagic {
+        file: PathBuf,
+        offset: usize,
+    },
+    TruncatedChunk {
+        file: PathBuf,
+        offset: usize,
+        length: usize,
+    },
+    BadHuffmanTree {
+        file: PathBuf,
+        offset: usize,
+        node: usize,
+    },
+    DecompressionOverrun {
+        file: PathBuf,
+        offset: usize,
+    },
+    UnsupportedMapSize {
+        file: PathBuf,
+        offset: usize,
+        width: u16,
+        height: u16,
+    },
+    UnknownVersion {
+        file: PathBuf,
+    },
+    MissingMap {
+        file: PathBuf,
+        episode: usize,
+        level: usize,
+    },
+    MissingChunk {
+        file: PathBuf,
+        chunk: usize,
+    },
+}
 
-    let mut huff: Vec<(u16, u16)> = Vec::new();
+impl CacheError {
+    pub fn file(&self) -> &Path {
+        match self {
+            CacheError::MissingFile { file, .. }
+            | CacheError::BadMagic { file, .. }
+            | CacheError::TruncatedChunk { file, .. }
+            | CacheError::BadHuffmanTree { file, .. }
+            | CacheError::DecompressionOverrun { file, .. }
+            | CacheError::UnsupportedMapSize { file, .. }
+            | CacheError::UnknownVersion { file }
+            | CacheError::MissingMap { file, .. }
+            | CacheError::MissingChunk { file, .. } => file,
+        }
+    }
 
-    for i in huff_file.chunks_exact(4) {
-        let bit0 = u16::from_le_bytes([i[0], i[1]]);
-        let bit1 = u16::from_le_bytes([i[2], i[3]]);
-        huff.push((bit0, bit1));
+    pub fn offset(&self) -> Option<usize> {
+        match self {
+            CacheError::MissingFile { .. }
+            | CacheError::UnknownVersion { .. }
+            | CacheError::MissingMap { .. }
+            | CacheError::MissingChunk { .. } => None,
+            CacheError::BadMagic { offset, .. }
+            | CacheError::TruncatedChunk { offset, .. }
+            | CacheError::BadHuffmanTree { offset, .. }
+            | CacheError::DecompressionOverrun { offset, .. }
+            | CacheError::UnsupportedMapSize { offset, .. } => Some(*offset),
+        }
     }
+}
 
-    let headers_file = fs::read(DATADIR.to_owned() + "/VGAHEAD.WL1")
-        .expect("Something went wrong reading the file");
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::MissingFile { file, source } => {
+                write!(f, "{}: could not read file: {}", file.display(), source)
+            }
+            CacheError::BadMagic { file, offset } => {
+                write!(f, "{}: bad magic at offset {:#x}", file.display(), offset)
+            }
+            CacheError::TruncatedChunk {
+                file,
+                offset,
+                length,
+            } => write!(
+                f,
+                "{}: truncated chunk, {} bytes needed at offset {:#x}",
+                file.display(),
+                length,
+                offset
+            ),
+            CacheError::BadHuffmanTree { file, offset, node } => write!(
+                f,
+                "{}: bad huffman tree node {} while decoding offset {:#x}",
+                file.display(),
+                node,
+                offset
+            ),
+            CacheError::DecompressionOverrun { file, offset } => write!(
+                f,
+                "{}: decompression overrun at offset {:#x}",
+                file.display(),
+                offset
+            ),
+            CacheError::UnsupportedMapSize {
+                file,
+                offset,
+                width,
+                height,
+            } => write!(
+                f,
+                "{}: unsupported map size {}x{} at offset {:#x}",
+                file.display(),
+                width,
+                height,
+                offset
+            ),
+            CacheError::UnknownVersion { file } => write!(
+                f,
+                "{}: no WL1, WL6, SOD or SDM data files found",
+                file.display()
+            ),
+            CacheError::MissingMap {
+                file,
+                episode,
+                level,
+            } => write!(
+                f,
+                "{}: no map for episode {} level {}",
+                file.display(),
+                episode + 1,
+                level + 1
+            ),
+            CacheError::MissingChunk { file, chunk } => {
+                write!(f, "{}: no chunk {}", file.display(), chunk)
+            }
+        }
+    }
+}
+
+impl error::Error for CacheError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CacheError::MissingFile { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
 
-    let mut buffer = [0u8; 4];
-    let mut headers: Vec<u32> = Vec::new();
+#[derive(Clone, Copy)]
+struct Chunk<'a> {
+    file: &'a Path,
+    offset: usize,
+    bytes: &'a [u8],
+}
 
-    for i in headers_file.chunks_exact(3) {
-        buffer[..3].copy_from_slice(i);
-        headers.push(u32::from_le_bytes(buffer));
+impl<'a> Chunk<'a> {
+    fn new(file: &'a Path, bytes: &'a [u8]) -> Self {
+        Chunk {
+            file,
+            offset: 0,
+            bytes,
+        }
     }
 
-    let graph_file = fs::read(DATADIR.to_owned() + "/VGAGRAPH.WL1")
-        .expect("Something went wrong reading the file");
+    fn len(&self) -> usize {
+        self.bytes.len()
+    }
 
-    let a = headers[1] as usize;
-    let pictable_bytes = huff_expand(&huff, &graph_file[4..a], (NUMPICS + 3) * 4);
+    fn slice(&self, start: usize, end: usize) -> Result<Chunk<'a>, CacheError> {
+        if start > end || end > self.bytes.len() {
+            return Err(self.truncated(start, end.saturating_sub(start)));
+        }
+        Ok(Chunk {
+            file: self.file,
+            offset: self.offset + start,
+            bytes: &self.bytes[start..end],
+        })
+    }
 
-    let mut pics: Vec<Picture> = Vec::new();
+    fn tail(&self, start: usize) -> Result<Chunk<'a>, CacheError> {
+        self.slice(start, self.bytes.len())
+    }
 
-    for chunk in STARTPICS..GETPSYCHEDPIC + 1 {
-        let i = (chunk - STARTPICS) * 4;
-        let width = u16::from_le_bytes([pictable_bytes[i], pictable_bytes[i + 1]]);
-        let height = u16::from_le_bytes([pictable_bytes[i + 2], pictable_bytes[i + 3]]);
-        let data = load_graphic(&graph_file, &headers, &huff, chunk);
+    fn byte_at(&self, pos: usize) -> Result<u8, CacheError> {
+        self.bytes
+            .get(pos)
+            .copied()
+            .ok_or_else(|| self.truncated(pos, 1))
+    }
 
-        pics.push(Picture {
-            width: width as u32,
-            height: height as u32,
-            data,
-        })
+    fn u16_at(&self, pos: usize) -> Result<u16, CacheError> {
+        let bytes = self.slice(pos, pos + 2)?.bytes;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
     }
 
-    let vswap_file =
-        fs::read(DATADIR.to_owned() + "/VSWAP.WL1").expect("Something went wrong reading the file");
+    fn u24_at(&self, pos: usize) -> Result<u32, CacheError> {
+        let bytes = self.slice(pos, pos + 3)?.bytes;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+    }
 
-    let chunks_in_file = u16::from_le_bytes([vswap_file[0], vswap_file[1]]) as usize;
-    let pm_sprite_start = u16::from_le_bytes([vswap_file[2], vswap_file[3]]) as usize;
-    let pm_sound_start = u16::from_le_bytes([vswap_file[4], vswap_file[5]]) as usize;
-    let mut page_offsets: Vec<u32> = Vec::new();
-    let mut page_lengths: Vec<u16> = Vec::new();
-    let offsets_start = 6;
-    let offsets_end = offsets_start + 4 * (chunks_in_file + 1);
-    let lengths_start = offsets_end;
-    let lengths_end = lengths_start + 2 * chunks_in_file;
+    fn u32_at(&self, pos: usize) -> Result<u32, CacheError> {
+        let bytes = self.slice(pos, pos + 4)?.bytes;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
 
-    for i in vswap_file[offsets_start..offsets_end].chunks_exact(4) {
-        let offset = u32::from_le_bytes(i.try_into().unwrap());
-        page_offsets.push(offset);
+    fn i32_at(&self, pos: usize) -> Result<i32, CacheError> {
+        Ok(self.u32_at(pos)? as i32)
     }
 
-    for i in vswap_file[lengths_start..lengths_end].chunks_exact(2) {
-        let length = u16::from_le_bytes(i.try_into().unwrap());
-        page_lengths.push(length);
+    fn truncated(&self, pos: usize, length: usize) -> CacheError {
+        CacheError::TruncatedChunk {
+            file: self.file.to_owned(),
+            offset: self.offset + pos,
+            length,
+        }
     }
 
-    let mut textures: Vec<Vec<u8>> = Vec::new();
-    let mut sprites: Vec<(CompShape, Vec<u8>)> = Vec::new();
-    let mut sounds: Vec<Vec<u8>> = Vec::new();
+    fn bad_magic(&self, pos: usize) -> CacheError {
+        CacheError::BadMagic {
+            file: self.file.to_owned(),
+            offset: self.offset + pos,
+        }
+    }
 
-    for i in 0..chunks_in_file - 1 {
-        if page_offsets[i] == 0 {
-            continue;
+    fn overrun(&self, pos: usize) -> CacheError {
+        CacheError::DecompressionOverrun {
+            file: self.file.to_owned(),
+            offset: self.offset + pos,
         }
+    }
+}
+
+struct DataFile {
+    path: PathBuf,
+    bytes: Vec<u8>,
+}
 
-        let mut value_end = page_offsets[i + 1] as usize;
-        if page_offsets[i + 1] == 0 {
-            value_end = page_offsets[i] as usize + page_lengths[i] as usize;
+impl DataFile {
+    fn read(path: PathBuf) -> Result<DataFile, CacheError> {
+        match fs::read(&path) {
+            Ok(bytes) => Ok(DataFile { path, bytes }),
+            Err(source) => Err(CacheError::MissingFile { file: path, source }),
         }
+    }
 
-        let value = &vswap_file[page_offsets[i] as usize..value_end];
-        if i < pm_sprite_start {
-            textures.push(value.to_vec());
-        } else if i < pm_sound_start {
-            if !value.is_empty() {
-                sprites.push((
-                    CompShape {
-                        left_pix: u16::from_le_bytes([value[0], value[1]]),
-                        right_pix: u16::from_le_bytes([value[2], value[3]]),
-                        dataofs: value[4..132]
-                            .chunks_exact(2)
-                            .into_iter()
-                            .map(|a| u16::from_le_bytes([a[0], a[1]]))
-                            .collect(),
-                    },
-                    value.to_vec(),
-                ));
-            }
+    fn chunk(&self) -> Chunk<'_> {
+        Chunk::new(&self.path, &self.bytes)
+    }
+
+    fn missing_chunk(&self, chunk: usize) -> CacheError {
+        CacheError::MissingChunk {
+            file: self.path.clone(),
+            chunk,
+        }
+    }
+}
+
+pub fn init<P: AsRef<Path>>(data_dir: P, version: GameVersion) -> Result<Cache, CacheError> {
+    let data_dir = data_dir.as_ref();
+    let huff_dict = DataFile::read(version.data_file(data_dir, "VGADICT"))?;
+
+    let mut huff: Vec<(u16, u16)> = Vec::new();
+
+    for i in 0..HUFF_NODES {
+        let bit0 = huff_dict.chunk().u16_at(i * 4)?;
+        let bit1 = huff_dict.chunk().u16_at(i * 4 + 2)?;
+        huff.push((bit0, bit1));
+    }
+
+    let graph_headers = DataFile::read(version.data_file(data_dir, "VGAHEAD"))?;
+    let graph = DataFile::read(version.data_file(data_dir, "VGAGRAPH"))?;
+
+    let pictable_end = graph_headers.chunk().u24_at(3)? as usize;
+    let num_pics = version
+        .num_pics()
+        .min(graph.chunk().u32_at(0)? as usize / 4);
+    let pictable = huff_expand(&huff, graph.chunk().slice(4, pictable_end)?, num_pics * 4)?
+        .chunks_exact(4)
+        .map(|i| {
+            (
+                u16::from_le_bytes([i[0], i[1]]),
+                u16::from_le_bytes([i[2], i[3]]),
+            )
+        })
+        .collect();
+
+    let vswap = DataFile::read(version.data_file(data_dir, "VSWAP"))?;
+    let header = vswap.chunk();
+
+    let chunks_in_file = header.u16_at(0)? as usize;
+    let sound_start = (header.u16_at(4)? as usize).min(chunks_in_file);
+    let sprite_start = (header.u16_at(2)? as usize).min(sound_start);
+    let offsets_start = 6;
+    let lengths_start = offsets_start + 4 * chunks_in_file;
+
+    let mut pages: Vec<(usize, usize)> = Vec::new();
+
+    for i in 0..chunks_in_file {
+        let offset = header.u32_at(offsets_start + i * 4)? as usize;
+        let length = header.u16_at(lengths_start + i * 2)? as usize;
+        if offset == 0 {
+            pages.push((0, 0));
         } else {
-            sounds.push(value.to_vec());
+            pages.push((offset, offset + length));
         }
     }
 
-    let maps = load_maps();
+    let map_head = DataFile::read(version.data_file(data_dir, "MAPHEAD"))?;
+    let map_head = parse_map_head(map_head.chunk())?;
+    let game_maps = DataFile::read(version.data_file(data_dir, "GAMEMAPS"))?;
+
+    let magic = game_maps.chunk().slice(0, GAMEMAPS_MAGIC.len())?;
+    if magic.bytes != GAMEMAPS_MAGIC {
+        return Err(magic.bad_magic(0));
+    }
 
-    Cache::new(pics, textures, sprites, sounds, maps)
+    Ok(Cache {
+        version,
+        huff,
+        graph,
+        graph_headers,
+        pics: (0..num_pics).map(|_| OnceCell::new()).collect(),
+        pictable,
+        vswap,
+        pages,
+        sprite_start,
+        sound_start,
+        sprites: (sprite_start..sound_start)
+            .map(|_| OnceCell::new())
+            .collect(),
+        maps: (0..NUM_MAPS).map(|_| OnceCell::new()).collect(),
+        map_head,
+        game_maps,
+    })
 }
 
-fn huff_expand(huff: &[(u16, u16)], source: &[u8], length: usize) -> Vec<u8> {
+fn huff_expand(huff: &[(u16, u16)], source: Chunk, length: usize) -> Result<Vec<u8>, CacheError> {
     let mut dest: Vec<u8> = Vec::new();
     let headptr = 254;
     let mut huffptr = headptr;
@@ -333,11 +760,16 @@ fn huff_expand(huff: &[(u16, u16)], source: &[u8], length: usize) -> Vec<u8> {
     let mut mask = 1;
     let mut nodeval;
 
-    loop {
-        if (source[i] & mask) == 0 {
-            nodeval = huff[huffptr].0;
+    while dest.len() < length {
+        let node = huff.get(huffptr).ok_or(CacheError::BadHuffmanTree {
+            file: source.file.to_owned(),
+            offset: source.offset + i,
+            node: huffptr,
+        })?;
+        if (source.byte_at(i)? & mask) == 0 {
+            nodeval = node.0;
         } else {
-            nodeval = huff[huffptr].1;
+            nodeval = node.1;
         }
         if mask == 0x80 {
             i += 1;
@@ -346,24 +778,26 @@ fn huff_expand(huff: &[(u16, u16)], source: &[u8], length: usize) -> Vec<u8> {
             mask <<= 1;
         }
         if nodeval < 256 {
-            dest.push(nodeval.try_into().unwrap());
+            dest.push(nodeval as u8);
             huffptr = headptr;
-            if dest.len() >= length {
-                break;
-            }
         } else {
             huffptr = nodeval as usize - 256;
         }
     }
-    dest
+    Ok(dest)
 }
 
-fn load_graphic(source: &[u8], headers: &[u32], huff: &[(u16, u16)], chunk: usize) -> Vec<u8> {
-    let pos = headers[chunk] as usize;
-    let end = headers[chunk + 1] as usize;
-
-    let length = u32::from_le_bytes(source[pos..pos + 4].try_into().unwrap());
-    huff_expand(huff, &source[pos + 4..end], length.try_into().unwrap())
+fn load_graphic(
+    source: Chunk,
+    headers: Chunk,
+    huff: &[(u16, u16)],
+    chunk: usize,
+) -> Result<Vec<u8>, CacheError> {
+    let pos = headers.u24_at(chunk * 3)? as usize;
+    let end = headers.u24_at((chunk + 1) * 3)? as usize;
+
+    let length = source.u32_at(pos)?;
+    huff_expand(huff, source.slice(pos + 4, end)?, length as usize)
 }
 
 #[derive(Debug)]
@@ -373,24 +807,24 @@ struct MapHead {
     title: Vec<u8>,
 }
 
-fn load_maps() -> Vec<Map> {
-    let map_metadata = parse_map_head(DATADIR.to_owned() + "/MAPHEAD.WL1");
-    parse_map_data(DATADIR.to_owned() + "/GAMEMAPS.WL1", map_metadata)
-}
-
-fn parse_map_head<P: AsRef<Path>>(path: P) -> MapHead {
-    let raw_data = fs::read(path).expect("could not read MAPHEAD file");
+fn parse_map_head(raw: Chunk) -> Result<MapHead, CacheError> {
+    let magic = raw.slice(0, 2)?.bytes;
+    if magic != RLEW_TAG {
+        return Err(raw.bad_magic(0));
+    }
 
-    MapHead {
-        magic: raw_data[0..2].try_into().unwrap(),
-        pointers: raw_data[2..=(4 * 100)]
-            .chunks_exact(4)
-            .take(NUM_MAPS)
-            .map(|x| i32::from_le_bytes(x.try_into().unwrap()))
-            .filter(|&x| x > 0)
-            .collect(),
-        title: raw_data[(2 + (4 * 100))..].to_owned(),
+    let mut pointers = Vec::new();
+    for i in 0..NUM_MAPS {
+        pointers.push(raw.i32_at(2 + 4 * i)?);
     }
+
+    Ok(MapHead {
+        magic: RLEW_TAG,
+        pointers,
+        title: raw
+            .tail(2 + (4 * 100))
+            .map_or(Vec::new(), |t| t.bytes.to_owned()),
+    })
 }
 
 #[derive(Debug)]
@@ -407,55 +841,46 @@ struct MapLevelHeader {
 }
 
 impl MapLevelHeader {
-    pub fn new(header_data: &[u8]) -> Self {
-        assert_eq!(header_data.len(), 38);
-        MapLevelHeader {
-            offset_plane0: i32::from_le_bytes(header_data[0..4].try_into().unwrap()),
-            offset_plane1: i32::from_le_bytes(header_data[4..8].try_into().unwrap()),
-            offset_plane2: i32::from_le_bytes(header_data[8..12].try_into().unwrap()),
-            length_plane0: u16::from_le_bytes(header_data[12..14].try_into().unwrap()),
-            length_plane1: u16::from_le_bytes(header_data[14..16].try_into().unwrap()),
-            length_plane2: u16::from_le_bytes(header_data[16..18].try_into().unwrap()),
-            width_n_tiles: u16::from_le_bytes(header_data[18..20].try_into().unwrap()),
-            height_n_tiles: u16::from_le_bytes(header_data[20..22].try_into().unwrap()),
-            name: String::from_utf8(header_data[22..].to_owned())
-                .expect("map level header name could not be parsed")
+    fn new(header_data: Chunk) -> Result<Self, CacheError> {
+        let header_data = header_data.slice(0, MAP_LEVEL_HEADER_LEN)?;
+        Ok(MapLevelHeader {
+            offset_plane0: header_data.i32_at(0)?,
+            offset_plane1: header_data.i32_at(4)?,
+            offset_plane2: header_data.i32_at(8)?,
+            length_plane0: header_data.u16_at(12)?,
+            length_plane1: header_data.u16_at(14)?,
+            length_plane2: header_data.u16_at(16)?,
+            width_n_tiles: header_data.u16_at(18)?,
+            height_n_tiles: header_data.u16_at(20)?,
+            name: String::from_utf8_lossy(header_data.tail(22)?.bytes)
                 .trim_end_matches(char::from(0))
                 .to_owned(),
-        }
+        })
     }
 }
 
-fn rlew_decompress(compressed_data: &[u8], magic_word: &[u8; 2]) -> Vec<u8> {
+fn rlew_decompress(compressed_data: Chunk, magic_word: &[u8; 2]) -> Result<Vec<u8>, CacheError> {
     let mut output = Vec::new();
     let mut word_i = 0;
     let n_words_max = compressed_data.len() / 2;
 
     while word_i < n_words_max {
         let offset = word_i * 2;
-        let word_bytes = &compressed_data[offset..(offset + 2)];
+        let word_bytes = compressed_data.slice(offset, offset + 2)?.bytes;
         if word_bytes == magic_word {
-            if word_i + 1 == n_words_max {
-                dbg!("malformed input?");
-                break;
-            }
-            let count = u16::from_le_bytes(
-                compressed_data[(offset + 2)..(offset + 4)]
-                    .try_into()
-                    .unwrap(),
-            ) as usize;
-            let value = &compressed_data[(offset + 4)..(offset + 6)];
+            let count = compressed_data.u16_at(offset + 2)? as usize;
+            let value = compressed_data.slice(offset + 4, offset + 6)?.bytes;
             output.extend(vec![value; count].concat());
             word_i += 3;
         } else {
-            output.extend_from_slice(&compressed_data[offset..(offset + 2)]);
+            output.extend_from_slice(word_bytes);
             word_i += 1;
         }
     }
-    output.into_iter().take(64 * 64 * 2).collect()
+    Ok(output.into_iter().take(64 * 64 * 2).collect())
 }
 
-fn carmack_decompress(compressed_data: &[u8]) -> Vec<u8> {
+fn carmack_decompress(compressed_data: Chunk) -> Result<Vec<u8>, CacheError> {
     const NEAR_POINTER: u8 = 0xA7;
     const FAR_POINTER: u8 = 0xA8;
     let mut output = Vec::new();
@@ -463,35 +888,37 @@ fn carmack_decompress(compressed_data: &[u8]) -> Vec<u8> {
     let mut n_shifts = 0;
     let mut offset = 0;
 
-    while offset < compressed_data.len() - 2 {
-        match &compressed_data[offset..(offset + 2)] {
+    while offset + 2 < compressed_data.len() {
+        let (segment_start, count) = match compressed_data.slice(offset, offset + 2)?.bytes {
             [0x00, NEAR_POINTER] | [0x00, FAR_POINTER] => {
-                output.push(compressed_data[offset + 2]);
-                output.push(compressed_data[offset + 1]);
+                output.push(compressed_data.byte_at(offset + 2)?);
+                output.push(compressed_data.byte_at(offset + 1)?);
                 n_shifts += 1;
+                (None, 0)
             }
             [count, NEAR_POINTER] => {
-                let distance = usize::from(compressed_data[offset + 2]);
-                let segment_start = output.len() - distance * 2;
-                let segment_end = segment_start + usize::from(*count) * 2;
-                let segment_to_repeat = output[segment_start..segment_end].to_vec();
-                output.extend_from_slice(&segment_to_repeat);
+                let distance = usize::from(compressed_data.byte_at(offset + 2)?);
                 n_shifts += 1;
+                (output.len().checked_sub(distance * 2), *count)
             }
             [count, FAR_POINTER] => {
-                let distance = u16::from_le_bytes(
-                    compressed_data[(offset + 2)..(offset + 4)]
-                        .try_into()
-                        .unwrap(),
-                );
-                let segment_start = (usize::from(distance) + 1) * 2;
-                let segment_end = segment_start + usize::from(*count) * 2;
-                let segment_to_repeat = output[segment_start..segment_end].to_vec();
-                output.extend_from_slice(&segment_to_repeat);
+                let distance = compressed_data.u16_at(offset + 2)?;
                 word_i += 1;
+                (Some((usize::from(distance) + 1) * 2), *count)
             }
             word_bytes => {
                 output.extend_from_slice(word_bytes);
+                (None, 0)
+            }
+        };
+
+        if count > 0 {
+            let segment_start = match segment_start {
+                Some(start) if start < output.len() => start,
+                _ => return Err(compressed_data.overrun(offset)),
+            };
+            for i in segment_start..segment_start + usize::from(count) * 2 {
+                output.push(output[i]);
             }
         }
 
@@ -500,62 +927,78 @@ fn carmack_decompress(compressed_data: &[u8]) -> Vec<u8> {
     }
 
     if offset < compressed_data.len() {
-        let remainder = &compressed_data[offset..];
+        let remainder = compressed_data.tail(offset)?.bytes;
         output.extend_from_slice(remainder);
     }
 
-    output
+    Ok(output)
 }
 
-fn get_plane(data: &[u8], offset: i32, length: u16, magic_rlew_word: &[u8; 2]) -> [[u16; 64]; 64] {
+fn get_plane(
+    data: Chunk,
+    offset: i32,
+    length: u16,
+    magic_rlew_word: &[u8; 2],
+) -> Result<[[u16; 64]; 64], CacheError> {
     let plane_start = offset as usize;
     let plane_end = plane_start + length as usize;
-    let decarmackized = carmack_decompress(&data[plane_start..plane_end]);
-    let bytes = rlew_decompress(&decarmackized[4..], magic_rlew_word);
+    let plane = data.slice(plane_start, plane_end)?;
+    let decarmackized = carmack_decompress(plane)?;
+    // Offsets into the expanded data mean nothing in the file, so any error
+    // past this point is reported against the start of the plane's chunk.
+    let bytes = Chunk::new(plane.file, &decarmackized)
+        .tail(4)
+        .and_then(|rlew_data| rlew_decompress(rlew_data, magic_rlew_word))
+        .map_err(|_| plane.overrun(0))?;
+    if bytes.len() < MAP_WIDTH * MAP_HEIGHT * 2 {
+        return Err(plane.overrun(0));
+    }
     let mut bytes = bytes
         .chunks_exact(2)
-        .map(|word| u16::from_le_bytes(word.try_into().unwrap()));
+        .map(|word| u16::from_le_bytes([word[0], word[1]]));
     let mut result = [[0; MAP_HEIGHT]; MAP_WIDTH];
     for y in 0..MAP_HEIGHT {
-        for x in result.iter_mut().take(MAP_WIDTH) {
-            x[y] = bytes.next().unwrap();
+        for (x, word) in result.iter_mut().zip(&mut bytes) {
+            x[y] = word;
         }
     }
-    result
+    Ok(result)
 }
 
-fn parse_map_data<P: AsRef<Path>>(path: P, meta: MapHead) -> Vec<Map> {
-    let raw_data = fs::read(path).expect("could not read GAMEMAPS file");
-    let mut maps = Vec::new();
-
-    for pointer in meta.pointers {
-        let pointer = pointer as usize;
-        let header = MapLevelHeader::new(&raw_data[pointer..(pointer + 38)]);
+fn parse_map(raw: Chunk, meta: &MapHead, index: usize) -> Result<Option<Map>, CacheError> {
+    let pointer = meta.pointers[index];
+    if pointer <= 0 {
+        return Ok(None);
+    }
 
-        if header.offset_plane0 == 0 {
-            continue;
-        }
+    let pointer = pointer as usize;
+    let header = MapLevelHeader::new(raw.tail(pointer)?)?;
 
-        assert_eq!(64, header.width_n_tiles);
-        assert_eq!(64, header.height_n_tiles);
+    if header.offset_plane0 == 0 {
+        return Ok(None);
+    }
 
-        maps.push(Map::new(
-            get_plane(
-                &raw_data,
-                header.offset_plane0,
-                header.length_plane0,
-                &meta.magic,
-            ),
-            get_plane(
-                &raw_data,
-                header.offset_plane1,
-                header.length_plane1,
-                &meta.magic,
-            ),
-            header.name,
-        ));
+    if header.width_n_tiles as usize != MAP_WIDTH || header.height_n_tiles as usize != MAP_HEIGHT {
+        return Err(CacheError::UnsupportedMapSize {
+            file: raw.file.to_owned(),
+            offset: pointer + 18,
+            width: header.width_n_tiles,
+            height: header.height_n_tiles,
+        });
     }
-    maps
+
+    let plane2 = if header.offset_plane2 > 0 && header.length_plane2 > 0 {
+        get_plane(raw, header.offset_plane2, header.length_plane2, &meta.magic)?
+    } else {
+        [[0; MAP_HEIGHT]; MAP_WIDTH]
+    };
+
+    Ok(Some(Map::new(
+        get_plane(raw, header.offset_plane0, header.length_plane0, &meta.magic)?,
+        get_plane(raw, header.offset_plane1, header.length_plane1, &meta.magic)?,
+        plane2,
+        header.name,
+    )))
 }
 
 #[cfg(test)]
@@ -563,21 +1006,37 @@ mod tests {
     use super::*;
     use std::io::Write;
 
+    fn chunk(bytes: &[u8]) -> Chunk<'_> {
+        Chunk::new(Path::new("GAMEMAPS.WL1"), bytes)
+    }
+
     #[test]
     fn test_rlew_decompress() {
         assert_eq!(
             rlew_decompress(
-                &[0x00, 0x01, 0x03, 0x04, 0xFE, 0xFE, 0x05, 0x00, 0xA0, 0x0A],
+                chunk(&[0x00, 0x01, 0x03, 0x04, 0xFE, 0xFE, 0x05, 0x00, 0xA0, 0x0A]),
                 &[0xFE, 0xFE]
-            ),
+            )
+            .unwrap(),
             &[0x00, 0x01, 0x03, 0x04, 0xA0, 0x0A, 0xA0, 0x0A, 0xA0, 0x0A, 0xA0, 0x0A, 0xA0, 0x0A]
         );
     }
 
+    #[test]
+    fn test_rlew_decompress_truncated() {
+        assert!(matches!(
+            rlew_decompress(chunk(&[0x00, 0x01, 0xFE, 0xFE, 0x05, 0x00]), &[0xFE, 0xFE]),
+            Err(CacheError::TruncatedChunk { offset: 6, .. })
+        ));
+    }
+
     #[test]
     fn test_carmack_decompress_escaped() {
         assert_eq!(
-            carmack_decompress(&[0x00, 0xA7, 0x12, 0xEE, 0xFF, 0x00, 0xA8, 0x34, 0xCC, 0xDD]),
+            carmack_decompress(chunk(&[
+                0x00, 0xA7, 0x12, 0xEE, 0xFF, 0x00, 0xA8, 0x34, 0xCC, 0xDD
+            ]))
+            .unwrap(),
             &[0x12, 0xA7, 0xEE, 0xFF, 0x34, 0xA8, 0xCC, 0xDD]
         );
     }
@@ -585,10 +1044,11 @@ mod tests {
     #[test]
     fn test_carmack_decompress_near_pointer() {
         assert_eq!(
-            carmack_decompress(&[
+            carmack_decompress(chunk(&[
                 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x04, 0xA7,
                 0x06, 0x00, 0x01
-            ]),
+            ]))
+            .unwrap(),
             &[
                 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x00, 0x01,
                 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x00, 0x01
@@ -599,11 +1059,12 @@ mod tests {
     #[test]
     fn test_carmack_decompress() {
         assert_eq!(
-            carmack_decompress(&[
+            carmack_decompress(chunk(&[
                 0xEA, 0xEB, 0xEC, 0x00, 0xA7, 0x14, 0xDE, 0xFA, 0x00, 0xA8, 0x34, 0xCC, 0xDD, 0xAB,
                 0x01, 0x03, 0xA7, 0x07, 0x00, 0x02, 0xA8, 0x0A, 0x00, 0x02, 0x02, 0x03, 0xFF, 0x0A,
                 0x2A, 0x00, 0xA7, 0xFF
-            ]),
+            ]))
+            .unwrap(),
             &[
                 0xEA, 0xEB, 0xEC, 0x00, 0xA7, 0x14, 0xDE, 0xFA, 0x34, 0xA8, 0xCC, 0xDD, 0xAB, 0x01,
                 0xEA, 0xEB, 0xEC, 0x00, 0xA7, 0x14, 0x00, 0x02, 0xA8, 0x0A, 0x00, 0x02, 0x02, 0x03,
@@ -613,18 +1074,84 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
+    fn test_carmack_decompress_overrun() {
+        assert!(matches!(
+            carmack_decompress(chunk(&[0xEA, 0xEB, 0x02, 0xA7, 0x05, 0x00, 0x00])),
+            Err(CacheError::DecompressionOverrun { offset: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_map_head_bad_magic() {
+        assert!(matches!(
+            parse_map_head(chunk(&[0x34, 0x12, 0x00, 0x00])),
+            Err(CacheError::BadMagic { offset: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn test_huff_expand_bad_tree() {
+        let mut huff = vec![(0, 0); HUFF_NODES];
+        huff[254] = (256 + 300, 256 + 300);
+        assert!(matches!(
+            huff_expand(&huff, chunk(&[0x00, 0x00]).tail(1).unwrap(), 1),
+            Err(CacheError::BadHuffmanTree {
+                offset: 1,
+                node: 300,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_get_plane_reports_chunk_offset() {
+        let raw = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x02, 0x00, 0xCD, 0xAB];
+        assert!(matches!(
+            get_plane(chunk(&raw), 10, 4, &RLEW_TAG),
+            Err(CacheError::DecompressionOverrun { offset: 10, .. })
+        ));
+    }
+
+    #[test]
     fn map_file_parsing() {
-        let maps = load_maps();
-        assert_eq!("Wolf1 Map1", maps[0].name);
-        assert_eq!("Wolf1 Map2", maps[1].name);
+        let cache = init(DATADIR, GameVersion::Shareware).unwrap();
+        assert_eq!("Wolf1 Map1", cache.get_map(0, 0).unwrap().name);
+        assert_eq!("Wolf1 Map2", cache.get_map(0, 1).unwrap().name);
+        assert!(matches!(
+            cache.get_map(1, 0),
+            Err(CacheError::MissingMap { .. })
+        ));
+
+        let map = cache.get_map(0, 0).unwrap();
+        let (x, y, _) = map.find_player_start();
+        assert!(map.area_at(x, y).is_some());
+        assert_eq!(None, map.area_at(0, 0));
+    }
+
+    #[test]
+    fn lazy_chunks_are_memoized() {
+        let cache = init(DATADIR, GameVersion::Shareware).unwrap();
+        assert!(cache.pics.iter().all(|pic| pic.get().is_none()));
+        let title = cache.get_pic(TITLEPIC).unwrap();
+        assert_eq!((320, 200), (title.width, title.height));
+        assert!(std::ptr::eq(title, cache.get_pic(TITLEPIC).unwrap()));
+        assert!(cache.get_sprite(0).is_ok());
+        assert!(cache.maps.iter().all(|map| map.get().is_none()));
+    }
+
+    #[test]
+    fn door_textures_sit_just_before_the_sprites() {
+        let cache = init(DATADIR, GameVersion::Shareware).unwrap();
+        assert_eq!(98, cache.door_wall());
+        assert!(cache.get_texture(cache.door_wall() + 7).is_ok());
+        assert!(cache.get_texture(cache.door_wall() + 8).is_err());
     }
 
     #[test]
     #[ignore]
     fn dump_map0_plane0_printout() {
-        let maps = load_maps();
+        let cache = init(DATADIR, GameVersion::Shareware).unwrap();
         let mut file = fs::File::create("test_map0.txt").unwrap();
-        write!(file, "{}", maps[0]).unwrap();
+        write!(file, "{}", cache.get_map(0, 0).unwrap()).unwrap();
     }
 }
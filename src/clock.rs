@@ -0,0 +1,66 @@
+use std::time::{Duration, Instant};
+
+// After a long stall (a breakpoint, the window being dragged) the game skips
+// ahead instead of running a burst of tics to catch up.
+const MAX_TICS_PER_FRAME: u32 = 10;
+
+/// Turns elapsed real time into a whole number of fixed-length game tics,
+/// carrying the remainder over to the next frame.
+pub struct Clock {
+    tic: Duration,
+    last: Instant,
+    pending: Duration,
+}
+
+impl Clock {
+    pub fn new(rate: u32, now: Instant) -> Self {
+        Self {
+            tic: Duration::from_secs(1) / rate,
+            last: now,
+            pending: Duration::ZERO,
+        }
+    }
+
+    /// How many tics to simulate for the time that passed until `now`.
+    pub fn advance(&mut self, now: Instant) -> u32 {
+        self.pending += now.saturating_duration_since(self.last);
+        self.last = now;
+
+        let tics = (self.pending.as_nanos() / self.tic.as_nanos()) as u32;
+        if tics > MAX_TICS_PER_FRAME {
+            self.pending = Duration::ZERO;
+            return MAX_TICS_PER_FRAME;
+        }
+        self.pending -= self.tic * tics;
+        tics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tic_count_does_not_depend_on_frame_rate() {
+        let start = Instant::now();
+        for fps in [30, 60, 144, 300] {
+            let mut clock = Clock::new(70, start);
+            let tics: u32 = (1..=fps * 2)
+                .map(|n| clock.advance(start + Duration::from_secs(2) * n / (fps * 2)))
+                .sum();
+            assert_eq!(140, tics, "at {} fps", fps);
+        }
+    }
+
+    #[test]
+    fn long_stalls_are_skipped() {
+        let start = Instant::now();
+        let mut clock = Clock::new(70, start);
+        assert_eq!(
+            MAX_TICS_PER_FRAME,
+            clock.advance(start + Duration::from_secs(5))
+        );
+        let next = start + Duration::from_secs(5) + Duration::from_millis(15);
+        assert_eq!(1, clock.advance(next));
+    }
+}
@@ -14,6 +14,8 @@ pub const STATUS_LINES: u32 = 0;
 pub const BASE_WIDTH: u32 = 320;
 pub const BASE_HEIGHT: u32 = 200;
 pub const WALLPIC_WIDTH: usize = 64;
+/// Game logic runs in fixed steps of 1/70 s, like the original.
+pub const TICRATE: u32 = 70;
 
 pub fn norm_angle(a: f64) -> f64 {
     let nrots = (a / (2.0 * PI)).trunc() - if a < 0.0 { 1.0 } else { 0.0 };
@@ -0,0 +1,273 @@
+use crate::constants::*;
+use crate::map::{Map, Tile, NUM_AREAS};
+use std::collections::HashMap;
+
+// Doors slide a full tile in 64 tics and stay open for 300 tics, the same
+// timing as the original game.
+const DOOR_SPEED: f64 = 1.0 / 64.0;
+const OPEN_TICS: u32 = 300;
+const FIRST_KEY_LOCK: u16 = 1;
+const LAST_KEY_LOCK: u16 = 4;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DoorState {
+    Closed,
+    Opening,
+    Open,
+    Closing,
+}
+
+#[derive(Debug, Clone)]
+pub struct Door {
+    pub x: u8,
+    pub y: u8,
+    pub vertical: bool,
+    pub lock: u16,
+    pub state: DoorState,
+    /// How far the door has slid into the wall, 0.0 closed and 1.0 open.
+    pub position: f64,
+    tics: u32,
+}
+
+impl Door {
+    fn new(x: u8, y: u8, vertical: bool, lock: u16) -> Self {
+        Self {
+            x,
+            y,
+            vertical,
+            lock,
+            state: DoorState::Closed,
+            position: 0.0,
+            tics: 0,
+        }
+    }
+
+    /// Whether a key is needed, and which one, to open this door.
+    pub fn key(&self) -> Option<u16> {
+        (FIRST_KEY_LOCK..=LAST_KEY_LOCK)
+            .contains(&self.lock)
+            .then(|| self.lock - FIRST_KEY_LOCK)
+    }
+
+    pub fn is_blocking(&self) -> bool {
+        self.state != DoorState::Open
+    }
+}
+
+pub struct Doors {
+    doors: Vec<Door>,
+    index: HashMap<(u8, u8), usize>,
+}
+
+impl Doors {
+    pub fn new(map: &Map) -> Self {
+        let mut doors = Vec::new();
+        let mut index = HashMap::new();
+        for x in 0..MAP_WIDTH as u8 {
+            for y in 0..MAP_HEIGHT as u8 {
+                if let Tile::Door { vertical, lock } = map.tile_at(x, y) {
+                    index.insert((x, y), doors.len());
+                    doors.push(Door::new(x, y, vertical, lock));
+                }
+            }
+        }
+        Self { doors, index }
+    }
+
+    pub fn at(&self, x: u8, y: u8) -> Option<&Door> {
+        self.index.get(&(x, y)).map(|&i| &self.doors[i])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Door> {
+        self.doors.iter()
+    }
+
+    /// The areas joined to `area` through doors that are not closed, the
+    /// ones sound carries into.
+    pub fn connected_areas(&self, map: &Map, area: u16) -> [bool; NUM_AREAS] {
+        let mut connected = [false; NUM_AREAS];
+        let mut pending = vec![area];
+        while let Some(area) = pending.pop() {
+            if connected[area as usize] {
+                continue;
+            }
+            connected[area as usize] = true;
+            for door in self
+                .doors
+                .iter()
+                .filter(|door| door.state != DoorState::Closed)
+            {
+                let (x, y) = (door.x as i32, door.y as i32);
+                let sides = if door.vertical {
+                    [(x - 1, y), (x + 1, y)]
+                } else {
+                    [(x, y - 1), (x, y + 1)]
+                };
+                let areas = sides.map(|(x, y)| {
+                    u8::try_from(x)
+                        .ok()
+                        .zip(u8::try_from(y).ok())
+                        .filter(|&(x, y)| (x as usize) < MAP_WIDTH && (y as usize) < MAP_HEIGHT)
+                        .and_then(|(x, y)| map.area_at(x, y))
+                });
+                if let [Some(a), Some(b)] = areas {
+                    if a == area || b == area {
+                        pending.push(if a == area { b } else { a });
+                    }
+                }
+            }
+        }
+        connected
+    }
+
+    /// Opens a closed door or closes an open one, as the use key does.
+    /// Locked doors only open if `keys` has the matching key bit set.
+    /// Returns false if there is no door at the tile or it stays locked.
+    pub fn operate(&mut self, x: u8, y: u8, keys: u8) -> bool {
+        let door = match self.index.get(&(x, y)) {
+            Some(&i) => &mut self.doors[i],
+            None => return false,
+        };
+        if let Some(key) = door.key() {
+            if keys & (1 << key) == 0 {
+                return false;
+            }
+        }
+        door.state = match door.state {
+            DoorState::Closed | DoorState::Closing => DoorState::Opening,
+            DoorState::Open | DoorState::Opening => DoorState::Closing,
+        };
+        door.tics = 0;
+        true
+    }
+
+    /// Opens a door for an enemy, locked or not, and keeps it from closing
+    /// for a while. Returns whether it is all the way open.
+    pub fn open(&mut self, x: u8, y: u8) -> bool {
+        let door = match self.index.get(&(x, y)) {
+            Some(&i) => &mut self.doors[i],
+            None => return true,
+        };
+        match door.state {
+            DoorState::Open => door.tics = 0,
+            DoorState::Opening => {}
+            DoorState::Closed | DoorState::Closing => door.state = DoorState::Opening,
+        }
+        door.state == DoorState::Open
+    }
+
+    /// Advances every door by one tic. `occupied` tells whether something is
+    /// standing in a tile, a door never closes on top of it.
+    pub fn update<F: Fn(u8, u8) -> bool>(&mut self, occupied: F) {
+        for door in &mut self.doors {
+            match door.state {
+                DoorState::Closed => {}
+                DoorState::Opening => {
+                    door.position += DOOR_SPEED;
+                    if door.position >= 1.0 {
+                        door.position = 1.0;
+                        door.state = DoorState::Open;
+                        door.tics = 0;
+                    }
+                }
+                DoorState::Open => {
+                    door.tics += 1;
+                    if door.tics >= OPEN_TICS && !occupied(door.x, door.y) {
+                        door.state = DoorState::Closing;
+                    }
+                }
+                DoorState::Closing => {
+                    if occupied(door.x, door.y) {
+                        door.state = DoorState::Opening;
+                        continue;
+                    }
+                    door.position -= DOOR_SPEED;
+                    if door.position <= 0.0 {
+                        door.position = 0.0;
+                        door.state = DoorState::Closed;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::AREATILE;
+
+    fn map_with_door(code: u16) -> Map {
+        let mut plane0 = [[0; MAP_HEIGHT]; MAP_WIDTH];
+        plane0[3][4] = code;
+        let empty = [[0; MAP_HEIGHT]; MAP_WIDTH];
+        Map::new(plane0, empty, empty, String::from("doors"))
+    }
+
+    fn tic(doors: &mut Doors, tics: u32, occupied: bool) {
+        for _ in 0..tics {
+            doors.update(|_, _| occupied);
+        }
+    }
+
+    #[test]
+    fn door_opens_waits_and_closes() {
+        let mut doors = Doors::new(&map_with_door(90));
+        assert!(doors.operate(3, 4, 0));
+        tic(&mut doors, 32, false);
+        let door = doors.at(3, 4).unwrap();
+        assert_eq!(door.state, DoorState::Opening);
+        assert!(door.is_blocking());
+
+        tic(&mut doors, 32, false);
+        assert_eq!(doors.at(3, 4).unwrap().state, DoorState::Open);
+
+        tic(&mut doors, OPEN_TICS, false);
+        assert_eq!(doors.at(3, 4).unwrap().state, DoorState::Closing);
+        tic(&mut doors, 64, false);
+        let door = doors.at(3, 4).unwrap();
+        assert_eq!(door.state, DoorState::Closed);
+        assert_eq!(door.position, 0.0);
+    }
+
+    #[test]
+    fn door_stays_open_while_occupied() {
+        let mut doors = Doors::new(&map_with_door(91));
+        doors.operate(3, 4, 0);
+        tic(&mut doors, 64 + OPEN_TICS * 2, true);
+        assert_eq!(doors.at(3, 4).unwrap().state, DoorState::Open);
+
+        tic(&mut doors, 10, false);
+        assert_eq!(doors.at(3, 4).unwrap().state, DoorState::Closing);
+        tic(&mut doors, 1, true);
+        assert_eq!(doors.at(3, 4).unwrap().state, DoorState::Opening);
+    }
+
+    #[test]
+    fn open_doors_connect_areas() {
+        let mut plane0 = [[AREATILE; MAP_HEIGHT]; MAP_WIDTH];
+        for column in plane0.iter_mut().skip(4) {
+            column.fill(AREATILE + 1);
+        }
+        plane0[3].fill(1);
+        plane0[3][4] = 90;
+        let empty = [[0; MAP_HEIGHT]; MAP_WIDTH];
+        let map = Map::new(plane0, empty, empty, String::from("areas"));
+        let mut doors = Doors::new(&map);
+        assert!(!doors.connected_areas(&map, 0)[1]);
+
+        doors.operate(3, 4, 0);
+        tic(&mut doors, 1, false);
+        let connected = doors.connected_areas(&map, 1);
+        assert!(connected[0] && connected[1] && !connected[2]);
+    }
+
+    #[test]
+    fn locked_door_needs_its_key() {
+        let mut doors = Doors::new(&map_with_door(94));
+        assert_eq!(doors.at(3, 4).unwrap().key(), Some(1));
+        assert!(!doors.operate(3, 4, 0b01));
+        assert!(doors.operate(3, 4, 0b10));
+        assert!(!doors.operate(5, 5, 0b11));
+    }
+}
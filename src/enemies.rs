@@ -0,0 +1,1196 @@
+use crate::constants::*;
+use crate::doors::Doors;
+use crate::map::{Actor, Boss, Direction, EnemyKind, Map, StaticKind, Tile, GD_HARD, NUM_AREAS};
+use crate::player::Player;
+use crate::pushwalls::PushWalls;
+use crate::random::Random;
+use crate::ray_caster;
+use crate::sprites::{self, *};
+use crate::weapons::Weapon;
+use std::f64::consts::PI;
+
+const TILE: f64 = MAP_SCALE_W as f64;
+// Map units per tic, the original's 512 and 1500 in 1/65536ths of a tile.
+const PATROL_SPEED: f64 = 512.0 / 65536.0 * TILE;
+const DOG_SPEED: f64 = 1500.0 / 65536.0 * TILE;
+// Enemies never walk closer to the player than a tile, and notice the
+// player within a tile and a half whichever way they face.
+const MIN_ACTOR_DIST: f64 = TILE;
+const MIN_SIGHT: f64 = 1.5 * TILE;
+// Animation tics of a walk cycle: a step, the pause after the first and
+// third steps, and the second and fourth steps.
+const PATH_TICS: [i32; 3] = [20, 5, 15];
+const CHASE_TICS: [i32; 3] = [10, 3, 8];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum State {
+    Stand,
+    Path,
+    Chase,
+    Attack,
+    Pain,
+    Die,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Shape {
+    /// Eight views, picked by the angle the enemy is seen from.
+    Rotated(usize),
+    Fixed(usize),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Think {
+    Nothing,
+    Stand,
+    Path,
+    Chase,
+    DogChase,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Act {
+    Nothing,
+    Shoot,
+    Bite,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum Next {
+    Frame,
+    Loop,
+    State(State),
+}
+
+/// One frame of an animation, the original's statetype. `think` runs on
+/// every tic the frame is shown and `act` once when it ends. Frames of 0
+/// tics only end when something else changes the state.
+#[derive(Debug, Copy, Clone)]
+struct Frame {
+    shape: Shape,
+    tics: i32,
+    think: Think,
+    act: Act,
+    next: Next,
+}
+
+impl Frame {
+    const fn new(shape: Shape, tics: i32) -> Self {
+        Self {
+            shape,
+            tics,
+            think: Think::Nothing,
+            act: Act::Nothing,
+            next: Next::Frame,
+        }
+    }
+
+    const fn think(self, think: Think) -> Self {
+        Self { think, ..self }
+    }
+
+    const fn act(self, act: Act) -> Self {
+        Self { act, ..self }
+    }
+
+    const fn then(self, state: State) -> Self {
+        Self {
+            next: Next::State(state),
+            ..self
+        }
+    }
+
+    const fn looped(self) -> Self {
+        Self {
+            next: Next::Loop,
+            ..self
+        }
+    }
+}
+
+const fn fixed(sprite: usize, tics: i32) -> Frame {
+    Frame::new(Shape::Fixed(sprite), tics)
+}
+
+const fn stand(sprite: usize) -> [Frame; 1] {
+    [Frame::new(Shape::Rotated(sprite), 0).think(Think::Stand)]
+}
+
+// Four steps through the walking sprites, eight rotations apart, pausing
+// after the first and the third.
+const fn walk_cycle(w1: usize, think: Think, tics: [i32; 3]) -> [Frame; 6] {
+    [
+        Frame::new(Shape::Rotated(w1), tics[0]).think(think),
+        Frame::new(Shape::Rotated(w1), tics[1]),
+        Frame::new(Shape::Rotated(w1 + 8), tics[2]).think(think),
+        Frame::new(Shape::Rotated(w1 + 16), tics[0]).think(think),
+        Frame::new(Shape::Rotated(w1 + 16), tics[1]),
+        Frame::new(Shape::Rotated(w1 + 24), tics[2])
+            .think(think)
+            .looped(),
+    ]
+}
+
+const fn pain(first: usize, second: usize) -> [Frame; 2] {
+    [
+        fixed(first, 10).then(State::Chase),
+        fixed(second, 10).then(State::Chase),
+    ]
+}
+
+struct Animations {
+    stand: &'static [Frame],
+    path: &'static [Frame],
+    chase: &'static [Frame],
+    attack: &'static [Frame],
+    /// Two alternatives picked by the hit points left, none for dogs.
+    pain: &'static [Frame],
+    die: &'static [Frame],
+}
+
+// The original's state tables.
+static GUARD: Animations = Animations {
+    stand: &stand(SPR_GRD_S_1),
+    path: &walk_cycle(SPR_GRD_W1_1, Think::Path, PATH_TICS),
+    chase: &walk_cycle(SPR_GRD_W1_1, Think::Chase, CHASE_TICS),
+    attack: &[
+        fixed(SPR_GRD_SHOOT1, 20),
+        fixed(SPR_GRD_SHOOT2, 20).act(Act::Shoot),
+        fixed(SPR_GRD_SHOOT3, 20).then(State::Chase),
+    ],
+    pain: &pain(SPR_GRD_PAIN_1, SPR_GRD_PAIN_2),
+    die: &[
+        fixed(SPR_GRD_DIE_1, 15),
+        fixed(SPR_GRD_DIE_2, 15),
+        fixed(SPR_GRD_DIE_3, 15),
+        fixed(SPR_GRD_DEAD, 0),
+    ],
+};
+
+static OFFICER: Animations = Animations {
+    stand: &stand(SPR_OFC_S_1),
+    path: &walk_cycle(SPR_OFC_W1_1, Think::Path, PATH_TICS),
+    chase: &walk_cycle(SPR_OFC_W1_1, Think::Chase, CHASE_TICS),
+    attack: &[
+        fixed(SPR_OFC_SHOOT1, 6),
+        fixed(SPR_OFC_SHOOT2, 20).act(Act::Shoot),
+        fixed(SPR_OFC_SHOOT3, 10).then(State::Chase),
+    ],
+    pain: &pain(SPR_OFC_PAIN_1, SPR_OFC_PAIN_2),
+    die: &[
+        fixed(SPR_OFC_DIE_1, 11),
+        fixed(SPR_OFC_DIE_2, 11),
+        fixed(SPR_OFC_DIE_3, 11),
+        fixed(SPR_OFC_DIE_4, 11),
+        fixed(SPR_OFC_DEAD, 0),
+    ],
+};
+
+static SS: Animations = Animations {
+    stand: &stand(SPR_SS_S_1),
+    path: &walk_cycle(SPR_SS_W1_1, Think::Path, PATH_TICS),
+    chase: &walk_cycle(SPR_SS_W1_1, Think::Chase, CHASE_TICS),
+    attack: &[
+        fixed(SPR_SS_SHOOT1, 20),
+        fixed(SPR_SS_SHOOT2, 20).act(Act::Shoot),
+        fixed(SPR_SS_SHOOT3, 10),
+        fixed(SPR_SS_SHOOT2, 10).act(Act::Shoot),
+        fixed(SPR_SS_SHOOT3, 10),
+        fixed(SPR_SS_SHOOT2, 10).act(Act::Shoot),
+        fixed(SPR_SS_SHOOT3, 10),
+        fixed(SPR_SS_SHOOT2, 10).act(Act::Shoot),
+        fixed(SPR_SS_SHOOT3, 10).then(State::Chase),
+    ],
+    pain: &pain(SPR_SS_PAIN_1, SPR_SS_PAIN_2),
+    die: &[
+        fixed(SPR_SS_DIE_1, 15),
+        fixed(SPR_SS_DIE_2, 15),
+        fixed(SPR_SS_DIE_3, 15),
+        fixed(SPR_SS_DEAD, 0),
+    ],
+};
+
+static DOG: Animations = Animations {
+    stand: &stand(SPR_DOG_W1_1),
+    path: &walk_cycle(SPR_DOG_W1_1, Think::Path, PATH_TICS),
+    chase: &walk_cycle(SPR_DOG_W1_1, Think::DogChase, CHASE_TICS),
+    attack: &[
+        fixed(SPR_DOG_JUMP1, 10),
+        fixed(SPR_DOG_JUMP2, 10).act(Act::Bite),
+        fixed(SPR_DOG_JUMP3, 10),
+        fixed(SPR_DOG_JUMP1, 10),
+        fixed(SPR_DOG_W1_1, 10).then(State::Chase),
+    ],
+    pain: &[],
+    die: &[
+        fixed(SPR_DOG_DIE_1, 15),
+        fixed(SPR_DOG_DIE_2, 15),
+        fixed(SPR_DOG_DIE_3, 15),
+        fixed(SPR_DOG_DEAD, 0),
+    ],
+};
+
+static MUTANT: Animations = Animations {
+    stand: &stand(SPR_MUT_S_1),
+    path: &walk_cycle(SPR_MUT_W1_1, Think::Path, PATH_TICS),
+    chase: &walk_cycle(SPR_MUT_W1_1, Think::Chase, CHASE_TICS),
+    attack: &[
+        fixed(SPR_MUT_SHOOT1, 6),
+        fixed(SPR_MUT_SHOOT2, 20).act(Act::Shoot),
+        fixed(SPR_MUT_SHOOT3, 10),
+        fixed(SPR_MUT_SHOOT4, 20).act(Act::Shoot).then(State::Chase),
+    ],
+    pain: &pain(SPR_MUT_PAIN_1, SPR_MUT_PAIN_2),
+    die: &[
+        fixed(SPR_MUT_DIE_1, 7),
+        fixed(SPR_MUT_DIE_2, 7),
+        fixed(SPR_MUT_DIE_3, 7),
+        fixed(SPR_MUT_DIE_4, 7),
+        fixed(SPR_MUT_DEAD, 0),
+    ],
+};
+
+/// The part of the game enemies look at and act on while they think.
+pub struct World<'a> {
+    pub map: &'a Map,
+    pub doors: &'a mut Doors,
+    pub pushwalls: &'a PushWalls,
+    pub player: &'a Player,
+    pub random: &'a mut Random,
+    /// The areas joined to the player's through open doors.
+    pub areas: [bool; NUM_AREAS],
+    /// Whether the player fired a gun this tic.
+    pub noise: bool,
+    /// The player's horizontal field of view in radians, enemies inside it
+    /// are on screen.
+    pub fov: f64,
+}
+
+// Where walking into a tile leads.
+enum Step {
+    Blocked,
+    Free,
+    Door,
+}
+
+/// An enemy or boss placed on the map, with what happened to it since.
+/// Only guards, officers, SS, dogs and mutants think. Bosses and ghosts
+/// stand still and never attack, though bosses can be shot dead.
+#[derive(Debug, Clone)]
+pub struct Enemy {
+    pub actor: Actor,
+    /// Position in map units.
+    pub x: f64,
+    pub y: f64,
+    pub hitpoints: i32,
+    /// Whether it is hunting the player.
+    pub alerted: bool,
+    pub state: State,
+    frame: usize,
+    tics: i32,
+    /// The way it is walking, `None` when it is stuck.
+    dir: Option<Direction>,
+    facing: Direction,
+    /// The tile it is walking into, and how far the middle of it still is.
+    goal: (u8, u8),
+    distance: f64,
+    waiting_for_door: bool,
+    speed: f64,
+    area: u16,
+    /// Only wakes up on seeing the player, not on hearing gunfire.
+    ambush: bool,
+    /// Tics left until it reacts to having noticed the player.
+    reaction: i32,
+    first_attack: bool,
+}
+
+impl Enemy {
+    /// The enemy for a map actor, or `None` if the actor is not one.
+    pub fn spawn(map: &Map, x: u8, y: u8, actor: Actor, difficulty: usize) -> Option<Self> {
+        let hitpoints = start_hitpoints(actor, difficulty.min(GD_HARD))?;
+        let (facing, patrolling, speed) = match actor {
+            Actor::Enemy(enemy) => {
+                let speed = match enemy.kind {
+                    EnemyKind::Dog => DOG_SPEED,
+                    _ => PATROL_SPEED,
+                };
+                (enemy.direction, enemy.patrolling, speed)
+            }
+            _ => (Direction::South, false, 0.0),
+        };
+        let mut spawned = Self {
+            actor,
+            x: TILE * (x as f64 + 0.5),
+            y: TILE * (y as f64 + 0.5),
+            hitpoints,
+            alerted: false,
+            state: State::Stand,
+            frame: 0,
+            tics: 0,
+            dir: Some(facing),
+            facing,
+            goal: (x, y),
+            distance: 0.0,
+            waiting_for_door: false,
+            speed,
+            area: map.area_at(x, y).unwrap_or(0),
+            ambush: !patrolling && map.is_ambush(x, y),
+            reaction: 0,
+            first_attack: false,
+        };
+        if patrolling {
+            // Patrols set off into the next tile without looking.
+            let (dx, dy) = delta(facing);
+            spawned.goal = ((x as i32 + dx) as u8, (y as i32 + dy) as u8);
+            spawned.distance = TILE;
+            spawned.enter(State::Path, 0);
+        } else {
+            spawned.enter(State::Stand, 0);
+        }
+        Some(spawned)
+    }
+
+    pub fn tile(&self) -> (u8, u8) {
+        (
+            (self.x / MAP_SCALE_W as f64) as u8,
+            (self.y / MAP_SCALE_H as f64) as u8,
+        )
+    }
+
+    /// Whether it stands in the tile or is walking into it, doors do not
+    /// close on it then.
+    pub fn occupies(&self, x: u8, y: u8) -> bool {
+        self.is_alive() && (self.goal == (x, y) || self.tile() == (x, y))
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.hitpoints > 0
+    }
+
+    /// Whether bullets can hurt it, the ghosts of the secret level are
+    /// immune.
+    pub fn is_shootable(&self) -> bool {
+        self.is_alive()
+            && !matches!(
+                self.actor,
+                Actor::Boss(Boss::Blinky | Boss::Clyde | Boss::Pinky | Boss::Inky)
+            )
+    }
+
+    /// Takes damage, double if it was caught unaware, and turns on the
+    /// player or dies. Returns whether this killed it.
+    pub fn damage(&mut self, damage: i32) -> bool {
+        if !self.is_alive() {
+            return false;
+        }
+        let damage = if self.alerted { damage } else { damage * 2 };
+        self.hitpoints -= damage;
+        if !self.is_alive() {
+            self.dir = None;
+            self.enter(State::Die, 0);
+            return true;
+        }
+        if !self.alerted {
+            self.first_sighting();
+        }
+        if self.animations().is_some_and(|a| !a.pain.is_empty()) {
+            let frame = if self.hitpoints & 1 == 1 { 0 } else { 1 };
+            self.enter(State::Pain, frame);
+        }
+        false
+    }
+
+    /// The points for killing it and the item it drops, as in the original.
+    /// SS drop a machine gun for a player who has none yet.
+    pub fn loot(&self, best: Weapon) -> (u32, Option<StaticKind>) {
+        match self.actor {
+            Actor::Enemy(enemy) => match enemy.kind {
+                EnemyKind::Guard => (100, Some(StaticKind::DroppedClip)),
+                EnemyKind::Officer => (400, Some(StaticKind::DroppedClip)),
+                EnemyKind::SS if best < Weapon::MachineGun => (500, Some(StaticKind::MachineGun)),
+                EnemyKind::SS => (500, Some(StaticKind::DroppedClip)),
+                EnemyKind::Dog => (200, None),
+                EnemyKind::Mutant => (700, None),
+            },
+            Actor::Boss(Boss::Hans | Boss::Gretel) => (5000, Some(StaticKind::GoldKey)),
+            Actor::Boss(Boss::FakeHitler) => (2000, None),
+            Actor::Boss(Boss::Blinky | Boss::Clyde | Boss::Pinky | Boss::Inky) => (0, None),
+            Actor::Boss(_) => (5000, None),
+            _ => (0, None),
+        }
+    }
+
+    pub fn sprite(&self, view_angle: f64) -> Option<usize> {
+        match self.animations() {
+            Some(_) => Some(match self.current().shape {
+                Shape::Rotated(sprite) => {
+                    sprite + sprites::rotation(view_angle, self.facing.angle())
+                }
+                Shape::Fixed(sprite) => sprite,
+            }),
+            None if self.is_alive() => sprites::actor_sprite(&self.actor, view_angle),
+            None => sprites::dead_sprite(&self.actor),
+        }
+    }
+
+    fn kind(&self) -> Option<EnemyKind> {
+        match self.actor {
+            Actor::Enemy(enemy) => Some(enemy.kind),
+            _ => None,
+        }
+    }
+
+    fn animations(&self) -> Option<&'static Animations> {
+        Some(match self.kind()? {
+            EnemyKind::Guard => &GUARD,
+            EnemyKind::Officer => &OFFICER,
+            EnemyKind::SS => &SS,
+            EnemyKind::Dog => &DOG,
+            EnemyKind::Mutant => &MUTANT,
+        })
+    }
+
+    fn frames(&self, state: State) -> &'static [Frame] {
+        let animations = match self.animations() {
+            Some(animations) => animations,
+            None => return &[],
+        };
+        match state {
+            State::Stand => animations.stand,
+            State::Path => animations.path,
+            State::Chase => animations.chase,
+            State::Attack => animations.attack,
+            State::Pain => animations.pain,
+            State::Die => animations.die,
+        }
+    }
+
+    fn current(&self) -> Frame {
+        self.frames(self.state)[self.frame]
+    }
+
+    fn enter(&mut self, state: State, frame: usize) {
+        self.state = state;
+        self.frame = frame;
+        self.tics = self.frames(state).get(frame).map_or(0, |frame| frame.tics);
+    }
+
+    /// Runs one tic of its animation and thinking, returning the damage it
+    /// dealt the player. `occupied` tells which tiles other enemies hold.
+    fn tic<F: Fn(u8, u8) -> bool>(&mut self, world: &mut World, occupied: &F) -> i32 {
+        if self.animations().is_none() {
+            return 0;
+        }
+        let mut damage = 0;
+        if self.tics > 0 {
+            self.tics -= 1;
+            while self.tics <= 0 {
+                let frame = self.current();
+                damage += self.act(frame.act, world);
+                match frame.next {
+                    Next::Frame => self.enter(self.state, self.frame + 1),
+                    Next::Loop => self.enter(self.state, 0),
+                    Next::State(state) => self.enter(state, 0),
+                }
+                if self.tics == 0 {
+                    break;
+                }
+            }
+        }
+        match self.current().think {
+            Think::Nothing => {}
+            Think::Stand => {
+                self.sight_player(world);
+            }
+            Think::Path => self.path(world, occupied),
+            Think::Chase => self.chase(world, occupied),
+            Think::DogChase => self.dog_chase(world, occupied),
+        }
+        damage
+    }
+
+    fn act(&mut self, act: Act, world: &mut World) -> i32 {
+        match act {
+            Act::Nothing => 0,
+            Act::Shoot => self.shoot(world),
+            Act::Bite => self.bite(world),
+        }
+    }
+
+    // Notices the player by sight or, unless lying in ambush, by gunfire in
+    // a connected area, and turns to attack after a moment to react.
+    fn sight_player(&mut self, world: &mut World) -> bool {
+        if self.reaction > 0 {
+            self.reaction -= 1;
+            if self.reaction > 0 {
+                return false;
+            }
+        } else {
+            if !world.areas[self.area as usize] {
+                return false;
+            }
+            if self.ambush {
+                if !self.check_sight(world) {
+                    return false;
+                }
+                self.ambush = false;
+            } else if !world.noise && !self.check_sight(world) {
+                return false;
+            }
+            let random = world.random.byte();
+            self.reaction = match self.kind() {
+                Some(EnemyKind::Guard) => 1 + random / 4,
+                Some(EnemyKind::Officer) => 2,
+                Some(EnemyKind::Mutant | EnemyKind::SS) => 1 + random / 6,
+                Some(EnemyKind::Dog) => 1 + random / 8,
+                None => 1,
+            };
+            return false;
+        }
+        self.first_sighting();
+        true
+    }
+
+    // Whether the player is close, or in front with nothing in between.
+    fn check_sight(&self, world: &World) -> bool {
+        let (dx, dy) = (world.player.x - self.x, world.player.y - self.y);
+        if dx.abs() < MIN_SIGHT && dy.abs() < MIN_SIGHT {
+            return true;
+        }
+        let (sin, cos) = self.facing.angle().sin_cos();
+        dx * sin + dy * cos >= 0.0 && self.in_line_of_sight(world)
+    }
+
+    fn in_line_of_sight(&self, world: &World) -> bool {
+        let player = (world.player.x, world.player.y);
+        ray_caster::visible(
+            world.map,
+            world.doors,
+            world.pushwalls,
+            (self.x, self.y),
+            player,
+        )
+    }
+
+    fn first_sighting(&mut self) {
+        self.speed *= match self.kind() {
+            Some(EnemyKind::Guard | EnemyKind::Mutant) => 3.0,
+            Some(EnemyKind::Officer) => 5.0,
+            Some(EnemyKind::SS) => 4.0,
+            Some(EnemyKind::Dog) => 2.0,
+            None => 1.0,
+        };
+        self.enter(State::Chase, 0);
+        self.alerted = true;
+        self.first_attack = true;
+    }
+
+    // Tiles between it and the player, along the longer axis.
+    fn tile_distance(&self, player: &Player) -> i32 {
+        let (x, y) = player.tile();
+        let dx = (x as i32 - self.goal.0 as i32).abs();
+        let dy = (y as i32 - self.goal.1 as i32).abs();
+        dx.max(dy)
+    }
+
+    fn path<F: Fn(u8, u8) -> bool>(&mut self, world: &mut World, occupied: &F) {
+        if self.sight_player(world) {
+            return;
+        }
+        if self.dir.is_none() {
+            self.select_path_dir(world, occupied);
+        }
+        self.walk(world, |enemy, world| enemy.select_path_dir(world, occupied));
+    }
+
+    fn chase<F: Fn(u8, u8) -> bool>(&mut self, world: &mut World, occupied: &F) {
+        let mut dodge = false;
+        if self.in_line_of_sight(world) {
+            // Closer means a better chance to open fire rather than move.
+            let distance = self.tile_distance(world.player);
+            let chance = if distance == 0 || (distance == 1 && self.distance < TILE / 4.0) {
+                300
+            } else {
+                16 / distance
+            };
+            if world.random.byte() < chance {
+                self.enter(State::Attack, 0);
+                return;
+            }
+            dodge = true;
+        }
+        let select = |enemy: &mut Self, world: &mut World| {
+            if dodge {
+                enemy.select_dodge_dir(world, occupied);
+            } else {
+                enemy.select_chase_dir(world, occupied);
+            }
+        };
+        if self.dir.is_none() {
+            select(self, world);
+        }
+        self.walk(world, select);
+    }
+
+    fn dog_chase<F: Fn(u8, u8) -> bool>(&mut self, world: &mut World, occupied: &F) {
+        if self.dir.is_none() {
+            self.select_dodge_dir(world, occupied);
+            if self.dir.is_none() {
+                return;
+            }
+        }
+        let mut step = self.speed;
+        while step > 0.0 {
+            let dx = (world.player.x - self.x).abs() - step;
+            let dy = (world.player.y - self.y).abs() - step;
+            if dx <= MIN_ACTOR_DIST && dy <= MIN_ACTOR_DIST {
+                self.enter(State::Attack, 0);
+                return;
+            }
+            if step < self.distance {
+                self.move_by(step, world);
+                return;
+            }
+            step -= self.arrive();
+            self.select_dodge_dir(world, occupied);
+            if self.dir.is_none() {
+                return;
+            }
+        }
+    }
+
+    // Moves a tic's worth towards the goal tile, picking the next one with
+    // `select` on the way. Waits for doors to open all the way first.
+    fn walk<S: Fn(&mut Self, &mut World)>(&mut self, world: &mut World, select: S) {
+        let mut step = self.speed;
+        while step > 0.0 && self.dir.is_some() {
+            if self.waiting_for_door {
+                if !world.doors.open(self.goal.0, self.goal.1) {
+                    return;
+                }
+                self.waiting_for_door = false;
+                self.distance = TILE;
+            }
+            if step < self.distance {
+                self.move_by(step, world);
+                return;
+            }
+            step -= self.arrive();
+            select(self, world);
+        }
+    }
+
+    // Snaps to the middle of the goal tile, returning how far that was.
+    fn arrive(&mut self) -> f64 {
+        self.x = TILE * (self.goal.0 as f64 + 0.5);
+        self.y = TILE * (self.goal.1 as f64 + 0.5);
+        self.distance
+    }
+
+    // Steps along its direction unless that would bump into the player.
+    fn move_by(&mut self, step: f64, world: &World) {
+        let (dx, dy) = match self.dir {
+            Some(dir) => delta(dir),
+            None => return,
+        };
+        let (x, y) = (self.x + dx as f64 * step, self.y + dy as f64 * step);
+        if world.areas[self.area as usize]
+            && (x - world.player.x).abs() <= MIN_ACTOR_DIST
+            && (y - world.player.y).abs() <= MIN_ACTOR_DIST
+        {
+            return;
+        }
+        self.x = x;
+        self.y = y;
+        self.distance -= step;
+    }
+
+    fn step_into<F: Fn(u8, u8) -> bool>(
+        &self,
+        world: &World,
+        occupied: &F,
+        x: i32,
+        y: i32,
+    ) -> Step {
+        if !(0..MAP_WIDTH as i32).contains(&x) || !(0..MAP_HEIGHT as i32).contains(&y) {
+            return Step::Blocked;
+        }
+        let (x, y) = (x as u8, y as u8);
+        match world.map.tile_at(x, y) {
+            Tile::Wall(_) => Step::Blocked,
+            Tile::Door { .. } => Step::Door,
+            Tile::Floor => {
+                let blocking = matches!(
+                    world.map.actor_at(x, y),
+                    Some(Actor::Static(kind)) if kind.is_blocking()
+                );
+                if blocking || occupied(x, y) {
+                    Step::Blocked
+                } else {
+                    Step::Free
+                }
+            }
+        }
+    }
+
+    // Sets off towards the next tile in `dir` if it can get there. Doors
+    // only let it through straight on, and never dogs.
+    fn try_walk<F: Fn(u8, u8) -> bool>(
+        &mut self,
+        dir: Direction,
+        world: &mut World,
+        occupied: &F,
+    ) -> bool {
+        let (dx, dy) = delta(dir);
+        let (x, y) = (self.goal.0 as i32, self.goal.1 as i32);
+        let door = if dx != 0 && dy != 0 {
+            let free = [(x + dx, y), (x, y + dy), (x + dx, y + dy)]
+                .into_iter()
+                .all(|(x, y)| matches!(self.step_into(world, occupied, x, y), Step::Free));
+            if !free {
+                return false;
+            }
+            false
+        } else {
+            match self.step_into(world, occupied, x + dx, y + dy) {
+                Step::Blocked => return false,
+                Step::Door if self.kind() == Some(EnemyKind::Dog) => return false,
+                Step::Door => true,
+                Step::Free => false,
+            }
+        };
+
+        self.dir = Some(dir);
+        self.facing = dir;
+        self.goal = ((x + dx) as u8, (y + dy) as u8);
+        if door {
+            world.doors.open(self.goal.0, self.goal.1);
+            self.waiting_for_door = true;
+        } else {
+            if let Some(area) = world.map.area_at(self.goal.0, self.goal.1) {
+                self.area = area;
+            }
+            self.distance = TILE;
+        }
+        true
+    }
+
+    // Patrols follow the arrows on the map, or keep going.
+    fn select_path_dir<F: Fn(u8, u8) -> bool>(&mut self, world: &mut World, occupied: &F) {
+        if let Some(Actor::PathTurn(dir)) = world.map.actor_at(self.goal.0, self.goal.1) {
+            self.dir = Some(dir);
+        }
+        let walking = match self.dir {
+            Some(dir) => self.try_walk(dir, world, occupied),
+            None => false,
+        };
+        if !walking {
+            self.dir = None;
+        }
+    }
+
+    // Heads for the player along the longer axis first, then anywhere but
+    // back, and turns around only as a last resort.
+    fn select_chase_dir<F: Fn(u8, u8) -> bool>(&mut self, world: &mut World, occupied: &F) {
+        let old = self.dir;
+        let turnaround = old.map(opposite);
+        let (px, py) = world.player.tile();
+        let dx = px as i32 - self.goal.0 as i32;
+        let dy = py as i32 - self.goal.1 as i32;
+        let mut towards = [direction(dx.signum(), 0), direction(0, dy.signum())];
+        if dy.abs() > dx.abs() {
+            towards.swap(0, 1);
+        }
+        let mut around = [
+            Direction::North,
+            Direction::East,
+            Direction::South,
+            Direction::West,
+        ];
+        if world.random.byte() <= 128 {
+            around.reverse();
+        }
+        let tries = towards
+            .into_iter()
+            .flatten()
+            .chain(old)
+            .chain(around)
+            .filter(|&dir| Some(dir) != turnaround)
+            .chain(turnaround);
+        for dir in tries {
+            if self.try_walk(dir, world, occupied) {
+                return;
+            }
+        }
+        self.dir = None;
+    }
+
+    // Zigzags towards the player while in its line of fire: the diagonal
+    // first, then the straight directions in a shuffled order of
+    // preference.
+    fn select_dodge_dir<F: Fn(u8, u8) -> bool>(&mut self, world: &mut World, occupied: &F) {
+        let turnaround = if self.first_attack {
+            self.first_attack = false;
+            None
+        } else {
+            self.dir.map(opposite)
+        };
+        let (px, py) = world.player.tile();
+        let dx = px as i32 - self.goal.0 as i32;
+        let dy = py as i32 - self.goal.1 as i32;
+        let (x, y) = (if dx > 0 { 1 } else { -1 }, if dy > 0 { 1 } else { -1 });
+        let mut tries = [(x, 0), (0, y), (-x, 0), (0, -y)];
+        if dx.abs() > dy.abs() {
+            tries.swap(0, 1);
+            tries.swap(2, 3);
+        }
+        if world.random.byte() < 128 {
+            tries.swap(0, 1);
+            tries.swap(2, 3);
+        }
+        let tries = [(x, y)]
+            .into_iter()
+            .chain(tries)
+            .filter_map(|(dx, dy)| direction(dx, dy))
+            .filter(|&dir| Some(dir) != turnaround)
+            .chain(turnaround);
+        for dir in tries {
+            if self.try_walk(dir, world, occupied) {
+                return;
+            }
+        }
+        self.dir = None;
+    }
+
+    // Fires at the player if in sight, missing more often from further
+    // away and when the player is not looking.
+    fn shoot(&mut self, world: &mut World) -> i32 {
+        if !world.areas[self.area as usize] || !self.in_line_of_sight(world) {
+            return 0;
+        }
+        let mut distance = self.tile_distance(world.player);
+        if self.kind() == Some(EnemyKind::SS) {
+            // Better shots.
+            distance = distance * 2 / 3;
+        }
+        let (dx, dy) = (self.x - world.player.x, self.y - world.player.y);
+        let off_view = norm_angle(dx.atan2(dy) - world.player.view_angle + PI) - PI;
+        let seen = off_view.abs() < world.fov / 2.0;
+        if world.random.byte() >= hit_chance(distance, seen, world.player.running) {
+            return 0;
+        }
+        let random = world.random.byte();
+        if distance < 2 {
+            random >> 2
+        } else if distance < 4 {
+            random >> 3
+        } else {
+            random >> 4
+        }
+    }
+
+    fn bite(&mut self, world: &mut World) -> i32 {
+        let dx = (world.player.x - self.x).abs() - TILE;
+        let dy = (world.player.y - self.y).abs() - TILE;
+        if dx <= MIN_ACTOR_DIST && dy <= MIN_ACTOR_DIST && world.random.byte() < 180 {
+            world.random.byte() >> 4
+        } else {
+            0
+        }
+    }
+}
+
+// Out of 256, the chance that a shot from `distance` tiles hits. Enemies on
+// screen aim better, and a running player is harder to hit.
+fn hit_chance(distance: i32, seen: bool, running: bool) -> i32 {
+    let base = if running { 160 } else { 256 };
+    if seen {
+        base - distance * 16
+    } else {
+        base - distance * 8
+    }
+}
+
+/// Runs one tic of every enemy, returning the damage they dealt the player.
+pub fn tic(enemies: &mut [Enemy], world: &mut World) -> i32 {
+    let mut damage = 0;
+    for i in 0..enemies.len() {
+        let (before, rest) = enemies.split_at_mut(i);
+        let (enemy, after) = rest.split_first_mut().expect("index is in range");
+        let occupied = |x, y| {
+            before
+                .iter()
+                .chain(after.iter())
+                .any(|other| other.occupies(x, y))
+        };
+        damage += enemy.tic(world, &occupied);
+    }
+    damage
+}
+
+// Tiles moved along x and y by a step in a direction.
+fn delta(dir: Direction) -> (i32, i32) {
+    match dir {
+        Direction::North => (0, -1),
+        Direction::East => (1, 0),
+        Direction::South => (0, 1),
+        Direction::West => (-1, 0),
+        Direction::NorthEast => (1, -1),
+        Direction::NorthWest => (-1, -1),
+        Direction::SouthEast => (1, 1),
+        Direction::SouthWest => (-1, 1),
+    }
+}
+
+fn direction(dx: i32, dy: i32) -> Option<Direction> {
+    [
+        Direction::North,
+        Direction::East,
+        Direction::South,
+        Direction::West,
+        Direction::NorthEast,
+        Direction::NorthWest,
+        Direction::SouthEast,
+        Direction::SouthWest,
+    ]
+    .into_iter()
+    .find(|&dir| delta(dir) == (dx, dy))
+}
+
+fn opposite(dir: Direction) -> Direction {
+    let (dx, dy) = delta(dir);
+    direction(-dx, -dy).expect("every direction has an opposite")
+}
+
+// Hit points per skill level, as in the original.
+fn start_hitpoints(actor: Actor, difficulty: usize) -> Option<i32> {
+    let by_skill = |points: [i32; 4]| Some(points[difficulty]);
+    match actor {
+        Actor::Enemy(enemy) => match enemy.kind {
+            EnemyKind::Guard => by_skill([25; 4]),
+            EnemyKind::Officer => by_skill([50; 4]),
+            EnemyKind::SS => by_skill([100; 4]),
+            EnemyKind::Dog => by_skill([1; 4]),
+            EnemyKind::Mutant => by_skill([45, 55, 55, 65]),
+        },
+        Actor::Boss(boss) => match boss {
+            Boss::Hans | Boss::Gretel | Boss::Giftmacher | Boss::Fettgesicht => {
+                by_skill([850, 950, 1050, 1200])
+            }
+            Boss::Schabbs => by_skill([850, 950, 1550, 2400]),
+            Boss::FakeHitler => by_skill([200, 300, 400, 500]),
+            Boss::MechaHitler => by_skill([800, 950, 1050, 1200]),
+            Boss::Blinky | Boss::Clyde | Boss::Pinky | Boss::Inky => by_skill([25; 4]),
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::doors::DoorState;
+    use crate::map::{AREATILE, GD_BABY};
+
+    // Guard codes on the easiest skill, facing east, north, west and south.
+    const GUARD_EAST: u16 = 108;
+
+    // Two rooms, areas 0 and 1, split by a wall at x = 10 with a door at
+    // (10, 5).
+    fn two_rooms(actors: &[(u8, u8, u16)]) -> Map {
+        let mut plane0 = [[AREATILE; MAP_HEIGHT]; MAP_WIDTH];
+        for column in plane0.iter_mut().skip(11) {
+            column.fill(AREATILE + 1);
+        }
+        plane0[10].fill(1);
+        plane0[10][5] = 90;
+        let mut plane1 = [[0; MAP_HEIGHT]; MAP_WIDTH];
+        for &(x, y, code) in actors {
+            plane1[x as usize][y as usize] = code;
+        }
+        let empty = [[0; MAP_HEIGHT]; MAP_WIDTH];
+        Map::new(plane0, plane1, empty, String::from("rooms"))
+    }
+
+    fn player_at(x: f64, y: f64) -> Player {
+        Player::new(TILE * x, TILE * y, ANGLE_LEFT)
+    }
+
+    fn guard(map: &Map, x: u8, y: u8) -> Enemy {
+        let actor = map.actor_at(x, y).unwrap();
+        Enemy::spawn(map, x, y, actor, GD_BABY).unwrap()
+    }
+
+    // Runs `tics` tics with the player in a fixed spot, returning the
+    // damage dealt.
+    fn run(
+        enemy: &mut Enemy,
+        map: &Map,
+        doors: &mut Doors,
+        player: &Player,
+        noise: bool,
+        tics: u32,
+    ) -> i32 {
+        let pushwalls = PushWalls::new(map);
+        let mut random = Random::new(1);
+        let (x, y) = player.tile();
+        let mut damage = 0;
+        for _ in 0..tics {
+            let areas = doors.connected_areas(map, map.area_at(x, y).unwrap());
+            let mut world = World {
+                map,
+                doors,
+                pushwalls: &pushwalls,
+                player,
+                random: &mut random,
+                areas,
+                noise,
+                fov: ray_caster::DEFAULT_FIELD_OF_VIEW,
+            };
+            damage += tic(std::slice::from_mut(enemy), &mut world);
+            doors.update(|x, y| enemy.occupies(x, y));
+        }
+        damage
+    }
+
+    #[test]
+    fn running_players_are_harder_to_hit() {
+        assert_eq!(256 - 3 * 16, hit_chance(3, true, false));
+        assert_eq!(256 - 3 * 8, hit_chance(3, false, false));
+        assert_eq!(160 - 3 * 16, hit_chance(3, true, true));
+        assert_eq!(160 - 3 * 8, hit_chance(3, false, true));
+    }
+
+    #[test]
+    fn guard_sees_what_is_in_front_and_hears_gunfire() {
+        let map = two_rooms(&[(5, 5, GUARD_EAST)]);
+        let mut doors = Doors::new(&map);
+
+        let mut enemy = guard(&map, 5, 5);
+        run(
+            &mut enemy,
+            &map,
+            &mut doors,
+            &player_at(2.5, 5.5),
+            false,
+            200,
+        );
+        assert_eq!(State::Stand, enemy.state);
+        run(
+            &mut enemy,
+            &map,
+            &mut doors,
+            &player_at(2.5, 5.5),
+            true,
+            200,
+        );
+        assert!(enemy.alerted);
+
+        let mut enemy = guard(&map, 5, 5);
+        run(
+            &mut enemy,
+            &map,
+            &mut doors,
+            &player_at(8.5, 2.5),
+            false,
+            200,
+        );
+        assert!(enemy.alerted);
+    }
+
+    #[test]
+    fn gunfire_does_not_carry_through_closed_doors() {
+        let map = two_rooms(&[(5, 5, GUARD_EAST)]);
+        let mut doors = Doors::new(&map);
+        let mut enemy = guard(&map, 5, 5);
+        run(
+            &mut enemy,
+            &map,
+            &mut doors,
+            &player_at(14.5, 5.5),
+            true,
+            200,
+        );
+        assert!(!enemy.alerted);
+
+        doors.operate(10, 5, 0);
+        run(
+            &mut enemy,
+            &map,
+            &mut doors,
+            &player_at(14.5, 5.5),
+            true,
+            200,
+        );
+        assert!(enemy.alerted);
+    }
+
+    #[test]
+    fn chasing_guard_opens_doors_and_shoots() {
+        let map = two_rooms(&[(5, 5, GUARD_EAST)]);
+        let mut doors = Doors::new(&map);
+        let mut enemy = guard(&map, 5, 5);
+        enemy.damage(0);
+        assert!(enemy.alerted);
+
+        let player = player_at(16.5, 8.5);
+        let damage = run(&mut enemy, &map, &mut doors, &player, false, 1000);
+        // Through the door, which has closed again behind it.
+        assert!(enemy.x > TILE * 11.0);
+        assert_eq!(DoorState::Closed, doors.at(10, 5).unwrap().state);
+        assert!(damage > 0);
+    }
+
+    #[test]
+    fn killed_enemies_give_points_and_drop_items() {
+        let map = two_rooms(&[(5, 5, GUARD_EAST), (6, 5, 126), (7, 5, 134)]);
+        let spawn = |x| {
+            let actor = map.actor_at(x, 5).unwrap();
+            Enemy::spawn(&map, x, 5, actor, GD_BABY).unwrap()
+        };
+        let (guard, ss, dog) = (spawn(5), spawn(6), spawn(7));
+        assert_eq!(
+            (100, Some(StaticKind::DroppedClip)),
+            guard.loot(Weapon::Pistol)
+        );
+        assert_eq!((500, Some(StaticKind::MachineGun)), ss.loot(Weapon::Pistol));
+        assert_eq!(
+            (500, Some(StaticKind::DroppedClip)),
+            ss.loot(Weapon::ChainGun)
+        );
+        assert_eq!((200, None), dog.loot(Weapon::Pistol));
+    }
+
+    #[test]
+    fn killed_guard_falls_and_stays_down() {
+        let map = two_rooms(&[(5, 5, GUARD_EAST)]);
+        let mut doors = Doors::new(&map);
+        let mut enemy = guard(&map, 5, 5);
+        enemy.damage(5);
+        assert_eq!(State::Pain, enemy.state);
+        assert!(enemy.damage(20));
+        assert!(!enemy.is_alive());
+        assert!(!enemy.damage(20));
+        assert_eq!(Some(SPR_GRD_DIE_1), enemy.sprite(0.0));
+
+        run(
+            &mut enemy,
+            &map,
+            &mut doors,
+            &player_at(8.5, 5.5),
+            false,
+            100,
+        );
+        assert_eq!(Some(SPR_GRD_DEAD), enemy.sprite(0.0));
+        assert!(!enemy.occupies(5, 5));
+    }
+
+    #[test]
+    fn frames_follow_the_viewing_angle() {
+        let map = two_rooms(&[(5, 5, GUARD_EAST)]);
+        let enemy = guard(&map, 5, 5);
+        // Seen from the east it shows its front, from the west its back.
+        assert_eq!(Some(SPR_GRD_S_1), enemy.sprite(ANGLE_LEFT));
+        assert_eq!(Some(SPR_GRD_S_1 + 4), enemy.sprite(ANGLE_RIGHT));
+    }
+}
@@ -0,0 +1,286 @@
+use crate::cache;
+use crate::constants::TICRATE;
+use crate::doors;
+use crate::enemies::{self, Enemy};
+use crate::map;
+use crate::player::Player;
+use crate::pushwalls;
+use crate::random::Random;
+use crate::ray_caster;
+use crate::weapons::{self, Shot, Weapon};
+use std::path::Path;
+use std::time::Instant;
+
+// How long the player lies dead before the level starts over.
+const DEATH_TICS: u32 = 2 * TICRATE;
+
+pub struct Game {
+    pub player: Player,
+    pub map: map::Map,
+    pub doors: doors::Doors,
+    pub pushwalls: pushwalls::PushWalls,
+    pub flats: map::Flats,
+    /// Everything on the map that is not an enemy: decorations, bonus
+    /// items, markers.
+    pub actors: Vec<(u8, u8, map::Actor)>,
+    pub enemies: Vec<Enemy>,
+    pub random: Random,
+    /// Whether the player fired a gun this tic, enemies in connected areas
+    /// hear it.
+    noise: bool,
+    /// The area the player was last seen in, kept while crossing doors.
+    player_area: u16,
+    /// Tics since the player died.
+    death_tics: u32,
+    pub episode: usize,
+    pub level: usize,
+    pub difficulty: usize,
+    /// The player's horizontal field of view in radians, enemies aim better
+    /// at a player who can see them.
+    pub fov: f64,
+    pub start_time: Instant,
+    pub cache: cache::Cache,
+}
+
+impl Game {
+    pub fn new(
+        data_dir: &Path,
+        version: cache::GameVersion,
+        episode: usize,
+        level: usize,
+        difficulty: usize,
+    ) -> Result<Self, cache::CacheError> {
+        let episode = episode - 1;
+        let level = level - 1;
+        let cache = cache::init(data_dir, version)?;
+        let map = cache.get_map(episode, level)?.clone();
+        let player = map.find_player();
+        let (x, y) = player.tile();
+        let player_area = map.area_at(x, y).unwrap_or(0);
+        let doors = doors::Doors::new(&map);
+        let pushwalls = pushwalls::PushWalls::new(&map);
+        let flats = map::Flats::new(&map);
+        let (enemies, actors) = populate(&map, difficulty);
+        Ok(Self {
+            cache,
+            map,
+            doors,
+            pushwalls,
+            flats,
+            actors,
+            enemies,
+            random: Random::new(1),
+            noise: false,
+            player_area,
+            death_tics: 0,
+            player,
+            episode,
+            level,
+            difficulty,
+            fov: ray_caster::DEFAULT_FIELD_OF_VIEW,
+            start_time: Instant::now(),
+        })
+    }
+
+    /// Whether the player died with no lives left.
+    pub fn is_over(&self) -> bool {
+        self.player.is_dead() && self.player.lives == 0
+    }
+
+    /// Opens the door or pushes the secret wall the player is facing.
+    pub fn operate(&mut self) {
+        if self.player.is_dead() {
+            return;
+        }
+        let (x, y) = self.player.facing_tile();
+        if !self.doors.operate(x, y, self.player.keys) {
+            let (dx, dy) = self.player.facing_direction();
+            let (player, enemies, actors) = (&self.player, &self.enemies, &self.actors);
+            self.pushwalls.push(&mut self.map, x, y, dx, dy, |x, y| {
+                blocks_pushwall(player, enemies, actors, x, y)
+            });
+        }
+    }
+
+    /// Readies one of the four weapons, if the player owns it.
+    pub fn select_weapon(&mut self, index: usize) {
+        if let Some(&weapon) = Weapon::ALL.get(index) {
+            self.player.weapons.select(weapon);
+        }
+    }
+
+    /// Advances the weapon by one tic with the trigger held or not, and
+    /// resolves the shot it fires against the enemy in the line of fire.
+    pub fn attack(&mut self, fire: bool) {
+        if self.player.is_dead() {
+            return;
+        }
+        let shot = match self.player.weapons.tic(fire) {
+            Some(shot) => shot,
+            None => return,
+        };
+        let target = weapons::target(
+            &self.player,
+            &self.enemies,
+            &self.map,
+            &self.doors,
+            &self.pushwalls,
+        );
+        let hit = match shot {
+            Shot::Bullet => {
+                self.noise = true;
+                target.and_then(|(i, _)| {
+                    weapons::bullet_damage(&self.player, &self.enemies[i], &mut self.random)
+                        .map(|damage| (i, damage))
+                })
+            }
+            Shot::Stab => target.and_then(|(i, distance)| {
+                weapons::stab_damage(distance, &mut self.random).map(|damage| (i, damage))
+            }),
+        };
+        if let Some((i, damage)) = hit {
+            if self.enemies[i].damage(damage) {
+                self.kill(i);
+            }
+        }
+    }
+
+    // Scores a kill and leaves what the enemy carried where it fell.
+    fn kill(&mut self, i: usize) {
+        let enemy = &self.enemies[i];
+        let (points, item) = enemy.loot(self.player.weapons.best);
+        self.player.give_points(points);
+        if let Some(kind) = item {
+            let (x, y) = enemy.tile();
+            self.actors.push((x, y, map::Actor::Static(kind)));
+        }
+    }
+
+    /// Picks up items where the player stands, then advances enemies, doors
+    /// and push-walls by one tic. Once the player is dead the level stands
+    /// still until it starts over, or for good when no lives are left.
+    pub fn update(&mut self) {
+        if self.player.is_dead() {
+            if !self.is_over() {
+                self.death_tics += 1;
+                if self.death_tics >= DEATH_TICS {
+                    self.restart_level();
+                }
+            }
+            return;
+        }
+        self.pick_up_items();
+        self.update_enemies();
+        self.update_doors();
+        self.update_pushwalls();
+    }
+
+    // Items the player has no use for yet stay on the map.
+    fn pick_up_items(&mut self) {
+        let (x, y) = self.player.tile();
+        let player = &mut self.player;
+        let mut picked = false;
+        self.actors.retain(|&(ax, ay, actor)| {
+            let bonus = match actor {
+                map::Actor::Static(kind) if (ax, ay) == (x, y) => kind.bonus(),
+                _ => None,
+            };
+            let taken = bonus.is_some_and(|bonus| player.pick_up(bonus));
+            picked |= taken;
+            !taken
+        });
+        // Dropped items were never on the map.
+        if picked && matches!(self.map.actor_at(x, y), Some(map::Actor::Static(_))) {
+            self.map.remove_actor(x, y);
+        }
+    }
+
+    fn update_enemies(&mut self) {
+        let (x, y) = self.player.tile();
+        if let Some(area) = self.map.area_at(x, y) {
+            self.player_area = area;
+        }
+        let areas = self.doors.connected_areas(&self.map, self.player_area);
+        let mut world = enemies::World {
+            map: &self.map,
+            doors: &mut self.doors,
+            pushwalls: &self.pushwalls,
+            player: &self.player,
+            random: &mut self.random,
+            areas,
+            noise: self.noise,
+            fov: self.fov,
+        };
+        let damage = enemies::tic(&mut self.enemies, &mut world);
+        self.player.take_damage(damage, self.difficulty);
+        self.noise = false;
+    }
+
+    // Puts the level back the way it was loaded and the player at its start
+    // with a life less.
+    fn restart_level(&mut self) {
+        // The map was read when the game started, the cache still holds it.
+        if let Ok(map) = self.cache.get_map(self.episode, self.level) {
+            self.map = map.clone();
+        }
+        let start = self.map.find_player();
+        self.player.respawn(start.x, start.y, start.view_angle);
+        let (x, y) = self.player.tile();
+        self.player_area = self.map.area_at(x, y).unwrap_or(0);
+        self.doors = doors::Doors::new(&self.map);
+        self.pushwalls = pushwalls::PushWalls::new(&self.map);
+        (self.enemies, self.actors) = populate(&self.map, self.difficulty);
+        self.noise = false;
+        self.death_tics = 0;
+    }
+
+    fn update_doors(&mut self) {
+        let (player, enemies) = (&self.player, &self.enemies);
+        self.doors.update(|x, y| is_occupied(player, enemies, x, y));
+    }
+
+    fn update_pushwalls(&mut self) {
+        let (player, enemies, actors) = (&self.player, &self.enemies, &self.actors);
+        let (x, y) = player.tile();
+        let floor = self.map.area_at(x, y).unwrap_or(0);
+        self.pushwalls.update(&mut self.map, floor, |x, y| {
+            blocks_pushwall(player, enemies, actors, x, y)
+        });
+    }
+}
+
+// Splits the map's actors into enemies and everything else, leaving out the
+// player start.
+fn populate(map: &map::Map, difficulty: usize) -> (Vec<Enemy>, Vec<(u8, u8, map::Actor)>) {
+    let mut enemies = Vec::new();
+    let mut actors = Vec::new();
+    for (x, y, actor) in map.actors(difficulty) {
+        if matches!(actor, map::Actor::Player(_)) {
+            continue;
+        }
+        match Enemy::spawn(map, x, y, actor, difficulty) {
+            Some(enemy) => enemies.push(enemy),
+            None => actors.push((x, y, actor)),
+        }
+    }
+    (enemies, actors)
+}
+
+fn is_occupied(player: &Player, enemies: &[Enemy], x: u8, y: u8) -> bool {
+    player.touches(x, y) || enemies.iter().any(|enemy| enemy.occupies(x, y))
+}
+
+// Push-walls also stop short of bonus items, which would be buried otherwise.
+fn blocks_pushwall(
+    player: &Player,
+    enemies: &[Enemy],
+    actors: &[(u8, u8, map::Actor)],
+    x: u8,
+    y: u8,
+) -> bool {
+    is_occupied(player, enemies, x, y)
+        || actors.iter().any(|&(ax, ay, actor)| {
+            (ax, ay) == (x, y)
+                && matches!(actor, map::Actor::Static(kind) if kind.bonus().is_some())
+        })
+}
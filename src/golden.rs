@@ -0,0 +1,317 @@
+//! Golden-image tests. Each scene loads a shareware level, places the player,
+//! renders a frame headlessly and compares it with a reference PNG under
+//! `tests/golden`. Run with `UPDATE_GOLDEN=1` to rewrite the references after
+//! an intended rendering change. On a mismatch the test writes an image to
+//! `target/golden-diff` with the differing pixels in red.
+
+use crate::cache::{self, GameVersion};
+use crate::constants::*;
+use crate::game::Game;
+use crate::map::{Actor, Flats, Map, Tile, AREATILE};
+use crate::player::{StraightMovement, MAX_HEALTH};
+use crate::renderer::Renderer;
+use crate::screenshot;
+use std::f64::consts::PI;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+const GOLDEN_DIR: &str = "tests/golden";
+const DIFF_DIR: &str = "target/golden-diff";
+// How far a channel may drift before a pixel counts as different, and the
+// share of different pixels a frame may have and still match.
+const CHANNEL_TOLERANCE: u8 = 8;
+const MAX_DIFFERENT: f64 = 0.002;
+
+fn load_game(episode: usize, level: usize) -> Game {
+    Game::new(
+        Path::new(cache::DATADIR),
+        GameVersion::Shareware,
+        episode,
+        level,
+        0,
+    )
+    .unwrap()
+}
+
+fn place(game: &mut Game, x: f64, y: f64, angle: f64) {
+    game.player.x = x * MAP_SCALE_W as f64;
+    game.player.y = y * MAP_SCALE_H as f64;
+    game.player.view_angle = angle;
+    game.player.move_angle = angle;
+}
+
+fn render(game: &Game) -> Renderer {
+    let mut renderer = Renderer::new(BASE_WIDTH, BASE_HEIGHT);
+    renderer.render(game);
+    renderer
+}
+
+fn load_png(path: &Path) -> Option<(u32, u32, Vec<u8>)> {
+    let decoder = png::Decoder::new(File::open(path).ok()?);
+    let mut reader = decoder.read_info().ok()?;
+    let mut rgb = vec![0; reader.output_buffer_size()];
+    reader.next_frame(&mut rgb).ok()?;
+    let (width, height) = reader.info().size();
+    Some((width, height, rgb))
+}
+
+fn write_diff(name: &str, renderer: &Renderer, expected: &[u8]) -> PathBuf {
+    let actual = renderer.rgb();
+    let diff: Vec<u8> = actual
+        .chunks(3)
+        .zip(expected.chunks(3))
+        .flat_map(|(a, e)| {
+            if differs(a, e) {
+                [255, 0, 0]
+            } else {
+                [a[0] / 3, a[1] / 3, a[2] / 3]
+            }
+        })
+        .collect();
+    fs::create_dir_all(DIFF_DIR).unwrap();
+    let path = Path::new(DIFF_DIR).join(format!("{}.png", name));
+    screenshot::write_png(&path, renderer.width, renderer.height, &diff).unwrap();
+    screenshot::save(
+        renderer,
+        &Path::new(DIFF_DIR).join(format!("{}-actual.png", name)),
+    )
+    .unwrap();
+    path
+}
+
+fn differs(a: &[u8], b: &[u8]) -> bool {
+    a.iter()
+        .zip(b)
+        .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE)
+}
+
+fn assert_golden(name: &str, game: &Game) {
+    assert_frame(name, &render(game));
+}
+
+fn assert_frame(name: &str, renderer: &Renderer) {
+    let path = Path::new(GOLDEN_DIR).join(format!("{}.png", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        screenshot::save(renderer, &path).unwrap();
+        return;
+    }
+
+    let (width, height, expected) = load_png(&path).unwrap_or_else(|| {
+        panic!(
+            "missing reference {}, run with UPDATE_GOLDEN=1",
+            path.display()
+        )
+    });
+    assert_eq!(
+        (renderer.width, renderer.height),
+        (width, height),
+        "{} has a different size",
+        path.display()
+    );
+    let different = renderer
+        .rgb()
+        .chunks(3)
+        .zip(expected.chunks(3))
+        .filter(|(a, e)| differs(a, e))
+        .count();
+    let share = different as f64 / (width * height) as f64;
+    if share > MAX_DIFFERENT {
+        let diff = write_diff(name, renderer, &expected);
+        panic!(
+            "{} differs from {} in {} pixels, see {}",
+            name,
+            path.display(),
+            different,
+            diff.display()
+        );
+    }
+}
+
+#[test]
+fn e1m1_start() {
+    let game = load_game(1, 1);
+    assert_golden("e1m1_start", &game);
+}
+
+// Textures from plane 2 for a map of one big area, `plane2` on every tile
+// and `level` as the level-wide entry.
+fn paint_flats(game: &mut Game, plane2: u16, level: u16) {
+    let mut planes = [[plane2; MAP_HEIGHT]; MAP_WIDTH];
+    planes[0][0] = level;
+    game.flats = Flats::new(&Map::new(
+        [[AREATILE; MAP_HEIGHT]; MAP_WIDTH],
+        [[0; MAP_HEIGHT]; MAP_WIDTH],
+        planes,
+        String::from("flats"),
+    ));
+}
+
+fn render_textured(game: &Game) -> Renderer {
+    let mut renderer = Renderer::new(BASE_WIDTH, BASE_HEIGHT);
+    renderer.textured_floors = true;
+    renderer.render(game);
+    renderer
+}
+
+#[test]
+fn e1m1_start_textured_floors() {
+    let mut game = load_game(1, 1);
+    // Floor wall 1 and ceiling wall 12 for the whole level.
+    paint_flats(&mut game, 0, 0x0c01);
+    assert_frame("e1m1_start_textured_floors", &render_textured(&game));
+}
+
+#[test]
+fn unpainted_flats_keep_the_flat_colors() {
+    let mut game = load_game(1, 1);
+    let flat = render(&game);
+    // The shipped levels leave plane 2 empty.
+    assert_eq!(flat.buffer, render_textured(&game).buffer);
+
+    // Every tile paints a ceiling but leaves its floor byte at zero.
+    paint_flats(&mut game, 0x0100, 0);
+    let ceilings_only = render_textured(&game);
+    let floor_start = (flat.pix_center * flat.width) as usize;
+    let floor_end = (flat.pix_height * flat.width) as usize;
+    assert_eq!(
+        flat.buffer[floor_start..floor_end],
+        ceilings_only.buffer[floor_start..floor_end]
+    );
+    assert_ne!(
+        flat.buffer[..floor_start],
+        ceilings_only.buffer[..floor_start]
+    );
+}
+
+#[test]
+fn e1m1_start_resized_to_ultrawide() {
+    let game = load_game(1, 1);
+    let mut renderer = Renderer::new(BASE_WIDTH, BASE_HEIGHT);
+    renderer.render(&game);
+    renderer.resize(560, 240);
+    renderer.render(&game);
+    assert_frame("e1m1_start_ultrawide", &renderer);
+}
+
+#[test]
+fn e1m1_start_looking_around() {
+    let mut game = load_game(1, 1);
+    let (x, y) = (game.player.x, game.player.y);
+    let angle = game.player.view_angle;
+    for (name, turn) in [
+        ("e1m1_left", PI / 2.0),
+        ("e1m1_back", PI),
+        ("e1m1_diagonal", 5.0 * PI / 4.0),
+    ] {
+        place(
+            &mut game,
+            x / MAP_SCALE_W as f64,
+            y / MAP_SCALE_H as f64,
+            norm_angle(angle + turn),
+        );
+        assert_golden(name, &game);
+    }
+}
+
+#[test]
+fn e1m1_face_against_a_wall() {
+    let mut game = load_game(1, 1);
+    let (x, y) = game.player.tile();
+    place(&mut game, x as f64 + 0.5, y as f64 + 0.5, ANGLE_UP);
+    // Walk up to the wall until the player bumps against it.
+    for _ in 0..TICRATE {
+        game.player.walk(
+            &game.map,
+            &game.doors,
+            Some(StraightMovement::Forward),
+            None,
+            0.0,
+            false,
+        );
+    }
+    assert_golden("e1m1_face_against_a_wall", &game);
+}
+
+#[test]
+fn e1m1_opening_door() {
+    let mut game = load_game(1, 1);
+    let door = game.doors.iter().next().unwrap().clone();
+    let (dx, dy) = if door.vertical {
+        (1.0, 0.0)
+    } else {
+        (0.0, 1.0)
+    };
+    let (x, y) = (door.x as f64 + 0.5 - dx, door.y as f64 + 0.5 - dy);
+    place(&mut game, x, y, dx.atan2(dy));
+    assert!(game.doors.operate(door.x, door.y, 0));
+    for _ in 0..32 {
+        game.update();
+    }
+    assert_golden("e1m1_door_half_open", &game);
+}
+
+#[test]
+fn e1m1_sliding_push_wall() {
+    let mut game = load_game(1, 1);
+    // Stand in front of the first push-wall that has room to slide away.
+    let (wall_x, wall_y, dx, dy) = game
+        .map
+        .actors(0)
+        .filter(|(_, _, actor)| *actor == Actor::PushWall)
+        .flat_map(|(x, y, _)| [(x, y, 1, 0), (x, y, -1, 0), (x, y, 0, 1), (x, y, 0, -1)])
+        .find(|&(x, y, dx, dy)| {
+            let floor = |step: i8| {
+                let (tx, ty) = (x as i8 + dx * step, y as i8 + dy * step);
+                matches!(game.map.tile_at(tx as u8, ty as u8), Tile::Floor)
+            };
+            floor(-1) && floor(1) && floor(2)
+        })
+        .unwrap();
+    let (from_x, from_y) = (wall_x as i8 - dx, wall_y as i8 - dy);
+    let angle = (dx as f64).atan2(dy as f64);
+    place(
+        &mut game,
+        from_x as f64 + 0.5,
+        from_y as f64 + 0.5,
+        norm_angle(angle),
+    );
+    assert!(game
+        .pushwalls
+        .push(&mut game.map, wall_x, wall_y, dx, dy, |_, _| false));
+    for _ in 0..96 {
+        game.update();
+    }
+    assert_golden("e1m1_push_wall", &game);
+}
+
+#[test]
+fn e1m1_restarts_after_death() {
+    let mut game = load_game(1, 1);
+    let (x, y) = game.player.tile();
+    place(&mut game, x as f64 + 0.5, y as f64 - 2.5, ANGLE_DOWN);
+    game.actors.clear();
+    game.player.take_damage(MAX_HEALTH, 1);
+    assert!(game.player.is_dead());
+    // The level stands still until it starts over from the player start.
+    game.update();
+    assert!(game.player.is_dead());
+    for _ in 0..2 * TICRATE {
+        game.update();
+    }
+    assert!(!game.player.is_dead());
+    assert!(!game.actors.is_empty());
+    assert_golden("e1m1_start", &game);
+
+    game.player.lives = 0;
+    game.player.take_damage(MAX_HEALTH, 1);
+    for _ in 0..4 * TICRATE {
+        game.update();
+    }
+    assert!(game.is_over());
+}
+
+#[test]
+fn e1m2_start() {
+    let game = load_game(1, 2);
+    assert_golden("e1m2_start", &game);
+}
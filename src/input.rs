@@ -0,0 +1,410 @@
+use crate::bindings::{Action, Bindings, Input};
+use crate::game::Game;
+use crate::player::{self, SideMovement, StraightMovement};
+use crate::pointer::Warp;
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window};
+
+// Radians turned per pixel of mouse movement at sensitivity 1.
+const MOUSE_TURN: f64 = 1.0 / 200.0;
+
+/// What the player is holding down, applied on every tic of a frame.
+pub struct Controls {
+    pub straight: Option<StraightMovement>,
+    pub side: Option<SideMovement>,
+    /// Radians to turn per tic, positive to the left.
+    pub turn: f64,
+    /// Radians the mouse turned the player since the last frame.
+    pub mouse_turn: f64,
+    pub run: bool,
+    pub fire: bool,
+    /// The weapon picked this frame, 0 to 3.
+    pub weapon: Option<usize>,
+    /// Actions that went down this frame.
+    pub pressed: Vec<Action>,
+}
+
+impl Controls {
+    /// Runs one tic of the game, turning by `extra_turn` on top of the keys.
+    /// The dead player does not move.
+    pub fn tic(&self, game: &mut Game, extra_turn: f64) {
+        if !game.player.is_dead() {
+            game.player.walk(
+                &game.map,
+                &game.doors,
+                self.straight,
+                self.side,
+                self.turn + extra_turn,
+                self.run,
+            );
+        }
+        game.attack(self.fire);
+        game.update();
+    }
+
+    /// Handles what happens once per press: opening the door or pushing
+    /// the secret wall in front of the player, and switching weapons.
+    pub fn operate(&self, game: &mut Game) {
+        if self.pressed(Action::Use) {
+            game.operate();
+        }
+        if let Some(weapon) = self.weapon {
+            game.select_weapon(weapon);
+        }
+    }
+
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+}
+
+/// Mouse turning. The cursor is hidden while the window has focus and
+/// turning follows how far it moved since the last frame. Where the pointer
+/// can be warped it goes back to the middle of the window every frame, so
+/// the player can keep turning past the edge of the screen.
+pub struct Mouse {
+    sensitivity: f64,
+    invert: bool,
+    last_x: Option<f32>,
+    captured: bool,
+    warp: Option<Warp>,
+}
+
+impl Mouse {
+    pub fn new(sensitivity: f64, invert: bool) -> Self {
+        Self {
+            sensitivity,
+            invert,
+            last_x: None,
+            captured: false,
+            warp: None,
+        }
+    }
+
+    /// Keeps the pointer in the middle of `window` where the platform
+    /// allows it.
+    pub fn warping(mut self, window: &Window) -> Self {
+        self.warp = Warp::new(window);
+        self
+    }
+
+    /// Radians to turn for a horizontal movement of `dx` pixels.
+    fn turn(&self, dx: f64) -> f64 {
+        let turn = -dx * MOUSE_TURN * self.sensitivity;
+        if self.invert {
+            -turn
+        } else {
+            turn
+        }
+    }
+
+    // The turn since the last frame, or nothing while the window is in the
+    // background, the cursor is given back then.
+    fn capture(&mut self, window: &mut Window) -> f64 {
+        let captured = window.is_active();
+        if captured != self.captured {
+            window.set_cursor_visibility(!captured);
+            self.captured = captured;
+        }
+        let x = window
+            .get_mouse_pos(MouseMode::Pass)
+            .filter(|_| captured)
+            .map(|(x, _)| x);
+        let turn = match (self.last_x, x) {
+            (Some(last), Some(x)) => self.turn((x - last) as f64),
+            _ => 0.0,
+        };
+        self.last_x = x;
+        if let Some(warp) = self.warp.as_ref().filter(|_| captured) {
+            let (width, height) = window.get_size();
+            let (center_x, center_y) = (width / 2, height / 2);
+            warp.to(center_x as i32, center_y as i32);
+            self.last_x = Some(center_x as f32);
+        }
+        turn
+    }
+}
+
+/// Turns keys and mouse buttons into actions through the bindings, and
+/// walks the player through rebinding them.
+pub struct Controller {
+    pub bindings: Bindings,
+    mouse: Option<Mouse>,
+    held: Vec<Action>,
+    buttons_held: Vec<MouseButton>,
+    /// The controls menu, while it is open.
+    rebinding: Option<RebindMenu>,
+}
+
+struct RebindMenu {
+    /// Index of the selected action in `Action::ALL`.
+    selected: usize,
+    /// Set while the next key or button goes to the selected action.
+    capture: Option<Capture>,
+}
+
+/// What a captured key or button does to the action's bindings.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Capture {
+    Replace,
+    Add,
+}
+
+/// Where the controls menu is after a frame.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Rebinding {
+    /// The menu is open with this action selected.
+    Choosing(Action),
+    /// The next key or button pressed, whichever it is, is bound to this
+    /// action. Escape cancels instead.
+    Waiting(Action, Capture),
+    Done,
+}
+
+impl Controller {
+    pub fn new(bindings: Bindings, mouse: Option<Mouse>) -> Self {
+        Self {
+            bindings,
+            mouse,
+            held: Vec::new(),
+            buttons_held: Vec::new(),
+            rebinding: None,
+        }
+    }
+
+    fn is_down(&self, window: &Window, input: Input) -> bool {
+        match input {
+            Input::Key(key) => window.is_key_down(key),
+            Input::Mouse(button) => window.get_mouse_down(button),
+        }
+    }
+
+    fn is_action_down(&self, window: &Window, action: Action) -> bool {
+        self.bindings
+            .get(action)
+            .iter()
+            .any(|&input| self.is_down(window, input))
+    }
+
+    // The mouse buttons that went down this frame.
+    fn buttons_pressed(&mut self, window: &Window) -> Vec<MouseButton> {
+        let held: Vec<MouseButton> = [MouseButton::Left, MouseButton::Middle, MouseButton::Right]
+            .into_iter()
+            .filter(|&button| window.get_mouse_down(button))
+            .collect();
+        let pressed = held
+            .iter()
+            .filter(|button| !self.buttons_held.contains(button))
+            .copied()
+            .collect();
+        self.buttons_held = held;
+        pressed
+    }
+
+    pub fn is_rebinding(&self) -> bool {
+        self.rebinding.is_some()
+    }
+
+    /// Opens the controls menu on the first action.
+    pub fn start_rebinding(&mut self) {
+        self.rebinding = Some(RebindMenu {
+            selected: 0,
+            capture: None,
+        });
+    }
+
+    /// Runs the controls menu for a frame with the key or mouse button
+    /// pressed in it.
+    pub fn rebind(&mut self, window: &Window) -> Rebinding {
+        let buttons = self.buttons_pressed(window);
+        let input = match window.get_keys_pressed(KeyRepeat::No).first() {
+            Some(&key) => Some(Input::Key(key)),
+            None => buttons.first().map(|&button| Input::Mouse(button)),
+        };
+        self.rebind_input(input)
+    }
+
+    // Up and Down pick an action. Enter waits for a key to replace its
+    // bindings with, Space for one to add to them, and Backspace restores
+    // its defaults. Escape closes the menu. While waiting, every key counts,
+    // Enter included, except Escape which cancels the wait.
+    fn rebind_input(&mut self, input: Option<Input>) -> Rebinding {
+        let menu = match &mut self.rebinding {
+            Some(menu) => menu,
+            None => return Rebinding::Done,
+        };
+        let action = Action::ALL[menu.selected];
+        match (menu.capture.take(), input) {
+            (capture, None) => menu.capture = capture,
+            (Some(_), Some(Input::Key(Key::Escape))) => {}
+            (Some(Capture::Replace), Some(input)) => self.bindings.set(action, vec![input]),
+            (Some(Capture::Add), Some(input)) => self.bindings.add(action, input),
+            (None, Some(Input::Key(key))) => match key {
+                Key::Up => {
+                    menu.selected = (menu.selected + Action::ALL.len() - 1) % Action::ALL.len()
+                }
+                Key::Down => menu.selected = (menu.selected + 1) % Action::ALL.len(),
+                Key::Enter => menu.capture = Some(Capture::Replace),
+                Key::Space => menu.capture = Some(Capture::Add),
+                Key::Backspace => self.bindings.reset(action),
+                Key::Escape => {
+                    self.rebinding = None;
+                    // Whatever is still held from rebinding must not fire now.
+                    self.held = Action::ALL.to_vec();
+                    return Rebinding::Done;
+                }
+                _ => {}
+            },
+            (None, Some(Input::Mouse(_))) => {}
+        }
+        let action = Action::ALL[menu.selected];
+        match menu.capture {
+            Some(capture) => Rebinding::Waiting(action, capture),
+            None => Rebinding::Choosing(action),
+        }
+    }
+
+    /// Reads which actions are held and which went down since the last
+    /// frame.
+    fn poll(&mut self, window: &Window) -> (Vec<Action>, Vec<Action>) {
+        self.buttons_pressed(window);
+        let held: Vec<Action> = Action::ALL
+            .into_iter()
+            .filter(|&action| self.is_action_down(window, action))
+            .collect();
+        let pressed = held
+            .iter()
+            .filter(|action| !self.held.contains(action))
+            .copied()
+            .collect();
+        self.held.clone_from(&held);
+        (held, pressed)
+    }
+}
+
+/// Reads the keyboard and mouse through the bindings. By default the left
+/// mouse button fires, the right one moves forward and the middle one opens
+/// doors, like in the original.
+pub fn process_input(window: &mut Window, controller: &mut Controller) -> Result<Controls, String> {
+    let (held, pressed) = controller.poll(window);
+    let down = |action| held.contains(&action);
+    if !window.is_open() || pressed.contains(&Action::Quit) {
+        return Err(String::from("Goodbye!"));
+    }
+
+    let mut straight: Option<StraightMovement> = None;
+    let mut side: Option<SideMovement> = None;
+    let mut turn = 0.0;
+    let run = down(Action::Run);
+
+    if down(Action::TurnLeft) {
+        if down(Action::Strafe) {
+            side = Some(SideMovement::StrafeLeft);
+        } else {
+            turn += player::ROTATE_SPEED;
+        }
+    }
+
+    if down(Action::TurnRight) {
+        if down(Action::Strafe) {
+            side = Some(SideMovement::StrafeRight);
+        } else {
+            turn -= player::ROTATE_SPEED;
+        }
+    }
+
+    if down(Action::Forward) {
+        straight = Some(StraightMovement::Forward);
+    }
+
+    if down(Action::Back) {
+        straight = Some(StraightMovement::Backward);
+    }
+
+    if down(Action::StrafeLeft) {
+        side = Some(SideMovement::StrafeLeft);
+    }
+
+    if down(Action::StrafeRight) {
+        side = Some(SideMovement::StrafeRight);
+    }
+
+    let weapon = [
+        Action::Weapon1,
+        Action::Weapon2,
+        Action::Weapon3,
+        Action::Weapon4,
+    ]
+    .iter()
+    .position(|action| pressed.contains(action));
+    let mouse_turn = match &mut controller.mouse {
+        Some(mouse) => mouse.capture(window),
+        None => 0.0,
+    };
+
+    Ok(Controls {
+        straight,
+        side,
+        turn,
+        mouse_turn,
+        run,
+        fire: down(Action::Fire),
+        weapon,
+        pressed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menu_rebinds_one_action_and_escape_cancels_the_wait() {
+        let mut controller = Controller::new(Bindings::default(), None);
+        controller.start_rebinding();
+        let mut press = |key| controller.rebind_input(Some(Input::Key(key)));
+        assert_eq!(Rebinding::Choosing(Action::Back), press(Key::Down));
+        assert_eq!(
+            Rebinding::Waiting(Action::Back, Capture::Add),
+            press(Key::Space)
+        );
+        assert_eq!(Rebinding::Choosing(Action::Back), press(Key::Escape));
+        assert_eq!(
+            Rebinding::Waiting(Action::Back, Capture::Add),
+            press(Key::Space)
+        );
+        assert_eq!(Rebinding::Choosing(Action::Back), press(Key::Z));
+        assert_eq!(Rebinding::Choosing(Action::Forward), press(Key::Up));
+        assert_eq!(
+            Rebinding::Waiting(Action::Forward, Capture::Replace),
+            press(Key::Enter)
+        );
+        assert_eq!(Rebinding::Choosing(Action::Forward), press(Key::Enter));
+        assert_eq!(Rebinding::Done, press(Key::Escape));
+
+        let bindings = &controller.bindings;
+        assert_eq!([Input::Key(Key::Enter)], bindings.get(Action::Forward));
+        assert_eq!(
+            [
+                Input::Key(Key::Down),
+                Input::Key(Key::S),
+                Input::Key(Key::Z)
+            ],
+            bindings.get(Action::Back)
+        );
+        assert_eq!(
+            Bindings::default().get(Action::Fire),
+            bindings.get(Action::Fire)
+        );
+        assert!(!controller.is_rebinding());
+    }
+
+    #[test]
+    fn mouse_turns_right_when_moved_right_unless_inverted() {
+        assert!(Mouse::new(1.0, false).turn(10.0) < 0.0);
+        assert!(Mouse::new(1.0, true).turn(10.0) > 0.0);
+        assert_eq!(
+            2.0 * Mouse::new(1.0, false).turn(-10.0),
+            Mouse::new(2.0, false).turn(-10.0)
+        );
+    }
+}
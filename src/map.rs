@@ -1,7 +1,21 @@
 use crate::constants::*;
 use crate::player;
+use crate::sprites::SPR_STAT_0;
+use std::f64::consts::PI;
 use std::fmt;
 
+pub const AMBUSHTILE: u16 = 106;
+pub const AREATILE: u16 = 107;
+pub const NUM_AREAS: usize = 37;
+pub const PUSHABLETILE: u16 = 98;
+pub const EXITTILE: u16 = 99;
+pub const ICONARROWS: u16 = 90;
+
+pub const GD_BABY: usize = 0;
+pub const GD_EASY: usize = 1;
+pub const GD_MEDIUM: usize = 2;
+pub const GD_HARD: usize = 3;
+
 #[derive(Copy, Clone)]
 pub enum Tile {
     Floor,
@@ -9,25 +23,336 @@ pub enum Tile {
     Door { vertical: bool, lock: u16 },
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Direction {
     North,
     East,
     South,
     West,
+    NorthEast,
+    NorthWest,
+    SouthEast,
+    SouthWest,
+}
+
+impl Direction {
+    const FROM_EAST: [Direction; 8] = [
+        Direction::East,
+        Direction::NorthEast,
+        Direction::North,
+        Direction::NorthWest,
+        Direction::West,
+        Direction::SouthWest,
+        Direction::South,
+        Direction::SouthEast,
+    ];
+
+    pub fn angle(self) -> f64 {
+        match self {
+            Direction::North => ANGLE_UP,
+            Direction::East => ANGLE_RIGHT,
+            Direction::South => ANGLE_DOWN,
+            Direction::West => ANGLE_LEFT,
+            Direction::NorthEast => 3.0 * PI / 4.0,
+            Direction::NorthWest => 5.0 * PI / 4.0,
+            Direction::SouthEast => PI / 4.0,
+            Direction::SouthWest => 7.0 * PI / 4.0,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Bonus {
+    GoldKey,
+    SilverKey,
+    DogFood,
+    Food,
+    FirstAid,
+    Clip,
+    DroppedClip,
+    MachineGun,
+    ChainGun,
+    Cross,
+    Chalice,
+    Chest,
+    Crown,
+    ExtraLife,
+    Gibs,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StaticKind {
+    Puddle,
+    GreenBarrel,
+    TableWithChairs,
+    FloorLamp,
+    Chandelier,
+    HangedSkeleton,
+    DogFood,
+    WhiteColumn,
+    GreenPlant,
+    Skeleton,
+    Sink,
+    BrownPlant,
+    Vase,
+    Table,
+    CeilingLight,
+    BrownUtensils,
+    Armor,
+    EmptyCage,
+    SkeletonCage,
+    Bones,
+    GoldKey,
+    SilverKey,
+    Bed,
+    Basket,
+    Food,
+    FirstAid,
+    Clip,
+    MachineGun,
+    ChainGun,
+    Cross,
+    Chalice,
+    Chest,
+    Crown,
+    ExtraLife,
+    BloodyBones,
+    Barrel,
+    Well,
+    EmptyWell,
+    BloodPool,
+    Flag,
+    AardwolfSign,
+    Bones1,
+    Bones2,
+    Bones3,
+    BlueUtensils,
+    Stove,
+    SpearRack,
+    Vines,
+    DroppedClip,
+}
+
+const STATICS: [(StaticKind, usize, bool, Option<Bonus>); 49] = [
+    (StaticKind::Puddle, SPR_STAT_0, false, None),
+    (StaticKind::GreenBarrel, SPR_STAT_0 + 1, true, None),
+    (StaticKind::TableWithChairs, SPR_STAT_0 + 2, true, None),
+    (StaticKind::FloorLamp, SPR_STAT_0 + 3, true, None),
+    (StaticKind::Chandelier, SPR_STAT_0 + 4, false, None),
+    (StaticKind::HangedSkeleton, SPR_STAT_0 + 5, true, None),
+    (
+        StaticKind::DogFood,
+        SPR_STAT_0 + 6,
+        false,
+        Some(Bonus::DogFood),
+    ),
+    (StaticKind::WhiteColumn, SPR_STAT_0 + 7, true, None),
+    (StaticKind::GreenPlant, SPR_STAT_0 + 8, true, None),
+    (StaticKind::Skeleton, SPR_STAT_0 + 9, false, None),
+    (StaticKind::Sink, SPR_STAT_0 + 10, true, None),
+    (StaticKind::BrownPlant, SPR_STAT_0 + 11, true, None),
+    (StaticKind::Vase, SPR_STAT_0 + 12, true, None),
+    (StaticKind::Table, SPR_STAT_0 + 13, true, None),
+    (StaticKind::CeilingLight, SPR_STAT_0 + 14, false, None),
+    (StaticKind::BrownUtensils, SPR_STAT_0 + 15, false, None),
+    (StaticKind::Armor, SPR_STAT_0 + 16, true, None),
+    (StaticKind::EmptyCage, SPR_STAT_0 + 17, true, None),
+    (StaticKind::SkeletonCage, SPR_STAT_0 + 18, true, None),
+    (StaticKind::Bones, SPR_STAT_0 + 19, false, None),
+    (
+        StaticKind::GoldKey,
+        SPR_STAT_0 + 20,
+        false,
+        Some(Bonus::GoldKey),
+    ),
+    (
+        StaticKind::SilverKey,
+        SPR_STAT_0 + 21,
+        false,
+        Some(Bonus::SilverKey),
+    ),
+    (StaticKind::Bed, SPR_STAT_0 + 22, true, None),
+    (StaticKind::Basket, SPR_STAT_0 + 23, false, None),
+    (StaticKind::Food, SPR_STAT_0 + 24, false, Some(Bonus::Food)),
+    (
+        StaticKind::FirstAid,
+        SPR_STAT_0 + 25,
+        false,
+        Some(Bonus::FirstAid),
+    ),
+    (StaticKind::Clip, SPR_STAT_0 + 26, false, Some(Bonus::Clip)),
+    (
+        StaticKind::MachineGun,
+        SPR_STAT_0 + 27,
+        false,
+        Some(Bonus::MachineGun),
+    ),
+    (
+        StaticKind::ChainGun,
+        SPR_STAT_0 + 28,
+        false,
+        Some(Bonus::ChainGun),
+    ),
+    (
+        StaticKind::Cross,
+        SPR_STAT_0 + 29,
+        false,
+        Some(Bonus::Cross),
+    ),
+    (
+        StaticKind::Chalice,
+        SPR_STAT_0 + 30,
+        false,
+        Some(Bonus::Chalice),
+    ),
+    (
+        StaticKind::Chest,
+        SPR_STAT_0 + 31,
+        false,
+        Some(Bonus::Chest),
+    ),
+    (
+        StaticKind::Crown,
+        SPR_STAT_0 + 32,
+        false,
+        Some(Bonus::Crown),
+    ),
+    (
+        StaticKind::ExtraLife,
+        SPR_STAT_0 + 33,
+        false,
+        Some(Bonus::ExtraLife),
+    ),
+    (
+        StaticKind::BloodyBones,
+        SPR_STAT_0 + 34,
+        false,
+        Some(Bonus::Gibs),
+    ),
+    (StaticKind::Barrel, SPR_STAT_0 + 35, true, None),
+    (StaticKind::Well, SPR_STAT_0 + 36, true, None),
+    (StaticKind::EmptyWell, SPR_STAT_0 + 37, true, None),
+    (
+        StaticKind::BloodPool,
+        SPR_STAT_0 + 38,
+        false,
+        Some(Bonus::Gibs),
+    ),
+    (StaticKind::Flag, SPR_STAT_0 + 39, true, None),
+    (StaticKind::AardwolfSign, SPR_STAT_0 + 40, true, None),
+    (StaticKind::Bones1, SPR_STAT_0 + 41, false, None),
+    (StaticKind::Bones2, SPR_STAT_0 + 42, false, None),
+    (StaticKind::Bones3, SPR_STAT_0 + 43, false, None),
+    (StaticKind::BlueUtensils, SPR_STAT_0 + 44, false, None),
+    (StaticKind::Stove, SPR_STAT_0 + 45, true, None),
+    (StaticKind::SpearRack, SPR_STAT_0 + 46, true, None),
+    (StaticKind::Vines, SPR_STAT_0 + 47, false, None),
+    (
+        StaticKind::DroppedClip,
+        SPR_STAT_0 + 26,
+        false,
+        Some(Bonus::DroppedClip),
+    ),
+];
+
+impl StaticKind {
+    pub fn from_code(code: u16) -> Option<StaticKind> {
+        let index = code.checked_sub(23)? as usize;
+        STATICS.get(index).map(|&(kind, _, _, _)| kind)
+    }
+
+    pub fn sprite(self) -> usize {
+        STATICS[self as usize].1
+    }
+
+    pub fn is_blocking(self) -> bool {
+        STATICS[self as usize].2
+    }
+
+    pub fn bonus(self) -> Option<Bonus> {
+        STATICS[self as usize].3
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EnemyKind {
+    Guard,
+    Officer,
+    SS,
+    Dog,
+    Mutant,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Enemy {
+    pub kind: EnemyKind,
+    pub patrolling: bool,
+    pub direction: Direction,
+    pub min_difficulty: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Boss {
+    Hans,
+    Schabbs,
+    FakeHitler,
+    MechaHitler,
+    Gretel,
+    Giftmacher,
+    Fettgesicht,
+    Blinky,
+    Clyde,
+    Pinky,
+    Inky,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Actor {
     Player(Direction),
-    Enemy,
-    Item,
+    Static(StaticKind),
+    Enemy(Enemy),
+    Boss(Boss),
     DeadGuard,
     PushWall,
+    PathTurn(Direction),
+    EndGame,
+}
+
+// Standing and patrolling enemies come in three bands of four facings, one
+// band per skill level: (stand, patrol, kind, gap between skill bands).
+const ENEMY_BANDS: [(u16, u16, EnemyKind, u16); 5] = [
+    (108, 112, EnemyKind::Guard, 36),
+    (116, 120, EnemyKind::Officer, 36),
+    (126, 130, EnemyKind::SS, 36),
+    (134, 138, EnemyKind::Dog, 36),
+    (216, 220, EnemyKind::Mutant, 18),
+];
+
+fn decode_enemy(code: u16) -> Option<Enemy> {
+    let skills = [(0, GD_BABY), (1, GD_MEDIUM), (2, GD_HARD)];
+    for (stand, patrol, kind, gap) in ENEMY_BANDS {
+        for (band, min_difficulty) in skills {
+            for (base, patrolling) in [(stand, false), (patrol, true)] {
+                let start = base + band * gap;
+                if (start..start + 4).contains(&code) {
+                    return Some(Enemy {
+                        kind,
+                        patrolling,
+                        direction: Direction::FROM_EAST[(code - start) as usize * 2],
+                        min_difficulty,
+                    });
+                }
+            }
+        }
+    }
+    None
 }
 
 #[derive(Debug, Clone)]
 pub struct Map {
     plane0: [[u16; MAP_HEIGHT]; MAP_WIDTH],
     plane1: [[u16; MAP_HEIGHT]; MAP_WIDTH],
+    plane2: [[u16; MAP_HEIGHT]; MAP_WIDTH],
     pub name: String,
 }
 
@@ -35,11 +360,13 @@ impl Map {
     pub fn new(
         plane0: [[u16; MAP_HEIGHT]; MAP_WIDTH],
         plane1: [[u16; MAP_HEIGHT]; MAP_WIDTH],
+        plane2: [[u16; MAP_HEIGHT]; MAP_WIDTH],
         name: String,
     ) -> Self {
         Self {
             plane0,
             plane1,
+            plane2,
             name,
         }
     }
@@ -55,43 +382,96 @@ impl Map {
                 vertical: false,
                 lock: (tile - 91) / 2,
             },
-            106 => Tile::Floor,
-            n if n < 107 => Tile::Wall(tile),
+            AMBUSHTILE => Tile::Floor,
+            n if n < AREATILE => Tile::Wall(tile),
             _ => Tile::Floor,
         }
     }
 
+    pub fn set_tile(&mut self, x: u8, y: u8, code: u16) {
+        self.plane0[x as usize][y as usize] = code;
+    }
+
+    pub fn remove_actor(&mut self, x: u8, y: u8) {
+        self.plane1[x as usize][y as usize] = 0;
+    }
+
+    pub fn plane2_at(&self, x: u8, y: u8) -> u16 {
+        self.plane2[x as usize][y as usize]
+    }
+
+    pub fn is_ambush(&self, x: u8, y: u8) -> bool {
+        self.plane0[x as usize][y as usize] == AMBUSHTILE
+    }
+
+    pub fn area_at(&self, x: u8, y: u8) -> Option<u16> {
+        match self.plane0[x as usize][y as usize] {
+            AMBUSHTILE => self.ambush_area(x as usize, y as usize),
+            n if n >= AREATILE && ((n - AREATILE) as usize) < NUM_AREAS => Some(n - AREATILE),
+            _ => None,
+        }
+    }
+
+    // Ambush markers replace the area code of their cell, the original game
+    // takes the area from the first neighbouring floor that has one.
+    fn ambush_area(&self, x: usize, y: usize) -> Option<u16> {
+        let neighbours = [
+            (x + 1, y),
+            (x, y.wrapping_sub(1)),
+            (x, y + 1),
+            (x.wrapping_sub(1), y),
+        ];
+        neighbours
+            .into_iter()
+            .filter(|&(nx, ny)| nx < MAP_WIDTH && ny < MAP_HEIGHT)
+            .map(|(nx, ny)| self.plane0[nx][ny])
+            .find(|&n| n >= AREATILE && ((n - AREATILE) as usize) < NUM_AREAS)
+            .map(|n| n - AREATILE)
+    }
+
     pub fn actor_at(&self, x: u8, y: u8) -> Option<Actor> {
         match self.plane1[x as usize][y as usize] {
             19 => Some(Actor::Player(Direction::North)),
             20 => Some(Actor::Player(Direction::East)),
             21 => Some(Actor::Player(Direction::South)),
             22 => Some(Actor::Player(Direction::West)),
-            n if (23..=72).contains(&n) => Some(Actor::Item),
-            98 => Some(Actor::PushWall),
+            n @ 23..=71 => StaticKind::from_code(n).map(Actor::Static),
+            n @ ICONARROWS..=97 => Some(Actor::PathTurn(
+                Direction::FROM_EAST[(n - ICONARROWS) as usize],
+            )),
+            PUSHABLETILE => Some(Actor::PushWall),
+            EXITTILE => Some(Actor::EndGame),
             124 => Some(Actor::DeadGuard),
-            n if n >= 108 => Some(Actor::Enemy),
-            _ => None,
+            160 => Some(Actor::Boss(Boss::FakeHitler)),
+            178 => Some(Actor::Boss(Boss::MechaHitler)),
+            179 => Some(Actor::Boss(Boss::Fettgesicht)),
+            196 => Some(Actor::Boss(Boss::Schabbs)),
+            197 => Some(Actor::Boss(Boss::Gretel)),
+            214 => Some(Actor::Boss(Boss::Hans)),
+            215 => Some(Actor::Boss(Boss::Giftmacher)),
+            224 => Some(Actor::Boss(Boss::Blinky)),
+            225 => Some(Actor::Boss(Boss::Clyde)),
+            226 => Some(Actor::Boss(Boss::Pinky)),
+            227 => Some(Actor::Boss(Boss::Inky)),
+            n => decode_enemy(n).map(Actor::Enemy),
         }
     }
 
+    pub fn actors(&self, difficulty: usize) -> impl Iterator<Item = (u8, u8, Actor)> + '_ {
+        (0..MAP_WIDTH as u8)
+            .flat_map(|x| (0..MAP_HEIGHT as u8).map(move |y| (x, y)))
+            .filter_map(|(x, y)| self.actor_at(x, y).map(|actor| (x, y, actor)))
+            .filter(move |(_, _, actor)| match actor {
+                Actor::Enemy(enemy) => enemy.min_difficulty <= difficulty,
+                _ => true,
+            })
+    }
+
     pub fn find_player(&self) -> player::Player {
         let (player_x, player_y, player_dir) = self.find_player_start();
         let player_x = (MAP_SCALE_W * (player_x as u32) + MAP_SCALE_W / 3) as f64;
         let player_y = (MAP_SCALE_H * (player_y as u32) + MAP_SCALE_H / 2) as f64;
-        let player_angle = match player_dir {
-            Direction::North => ANGLE_UP,
-            Direction::East => ANGLE_RIGHT,
-            Direction::South => ANGLE_DOWN,
-            Direction::West => ANGLE_LEFT,
-        };
-
-        player::Player {
-            x: player_x,
-            y: player_y,
-            view_angle: player_angle,
-            move_angle: player_angle,
-        }
+        player::Player::new(player_x, player_y, player_dir.angle())
     }
 
     pub fn find_player_start(&self) -> (u8, u8, Direction) {
@@ -106,6 +486,62 @@ impl Map {
     }
 }
 
+/// Floor and ceiling textures painted into plane 2, each entry holding wall
+/// numbers with the floor in the low byte and the ceiling in the high byte.
+/// A zero byte leaves that half unpainted. Each half of a tile uses its own
+/// entry, else the first one painted in its area, else the level-wide entry
+/// in the top left corner of the map.
+pub struct Flats {
+    tiles: [[(Option<u16>, Option<u16>); MAP_HEIGHT]; MAP_WIDTH],
+}
+
+impl Flats {
+    pub fn new(map: &Map) -> Self {
+        let floors = Self::resolve(map, |code| code & 0xff);
+        let ceilings = Self::resolve(map, |code| code >> 8);
+
+        let mut tiles = [[(None, None); MAP_HEIGHT]; MAP_WIDTH];
+        for (x, column) in tiles.iter_mut().enumerate() {
+            for (y, tile) in column.iter_mut().enumerate() {
+                *tile = (floors[x][y], ceilings[x][y]);
+            }
+        }
+        Self { tiles }
+    }
+
+    // Resolves one half of the entries, picked out by `wall`, for every tile.
+    fn resolve(map: &Map, wall: impl Fn(u16) -> u16) -> [[Option<u16>; MAP_HEIGHT]; MAP_WIDTH] {
+        let decode = |code: u16| Some(wall(code)).filter(|&wall| wall != 0);
+        let level = decode(map.plane2[0][0]);
+        let mut areas = [None; NUM_AREAS];
+        for x in 0..MAP_WIDTH as u8 {
+            for y in 0..MAP_HEIGHT as u8 {
+                if let Some(area) = map.area_at(x, y) {
+                    let entry = &mut areas[area as usize];
+                    *entry = entry.or(decode(map.plane2_at(x, y)));
+                }
+            }
+        }
+
+        let mut tiles = [[None; MAP_HEIGHT]; MAP_WIDTH];
+        for (x, column) in tiles.iter_mut().enumerate() {
+            for (y, tile) in column.iter_mut().enumerate() {
+                let area = map
+                    .area_at(x as u8, y as u8)
+                    .and_then(|area| areas[area as usize]);
+                *tile = decode(map.plane2[x][y]).or(area).or(level);
+            }
+        }
+        tiles
+    }
+
+    /// The floor and ceiling wall numbers for a tile, each `None` if it was
+    /// never painted.
+    pub fn at(&self, x: u8, y: u8) -> (Option<u16>, Option<u16>) {
+        self.tiles[x as usize][y as usize]
+    }
+}
+
 impl fmt::Display for Map {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for x in 0..MAP_WIDTH {
@@ -115,7 +551,7 @@ impl fmt::Display for Map {
                     write!(f, "|").unwrap();
                 } else if word == 91 {
                     write!(f, "-").unwrap();
-                } else if word < 107 {
+                } else if word < AREATILE {
                     write!(f, "W").unwrap();
                 } else {
                     write!(f, " ").unwrap();
@@ -126,3 +562,97 @@ impl fmt::Display for Map {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn map_with_actor(code: u16) -> Map {
+        let mut plane1 = [[0; MAP_HEIGHT]; MAP_WIDTH];
+        plane1[1][1] = code;
+        Map::new(
+            [[AREATILE + 1; MAP_HEIGHT]; MAP_WIDTH],
+            plane1,
+            [[0; MAP_HEIGHT]; MAP_WIDTH],
+            String::from("test"),
+        )
+    }
+
+    #[test]
+    fn decodes_enemy_bands() {
+        assert_eq!(
+            Some(Actor::Enemy(Enemy {
+                kind: EnemyKind::Guard,
+                patrolling: false,
+                direction: Direction::East,
+                min_difficulty: GD_BABY,
+            })),
+            map_with_actor(108).actor_at(1, 1)
+        );
+        assert_eq!(
+            Some(Actor::Enemy(Enemy {
+                kind: EnemyKind::SS,
+                patrolling: true,
+                direction: Direction::South,
+                min_difficulty: GD_MEDIUM,
+            })),
+            map_with_actor(169).actor_at(1, 1)
+        );
+        assert_eq!(0, map_with_actor(169).actors(GD_EASY).count());
+        assert_eq!(1, map_with_actor(169).actors(GD_MEDIUM).count());
+        assert_eq!(
+            Some(Actor::Enemy(Enemy {
+                kind: EnemyKind::Mutant,
+                patrolling: true,
+                direction: Direction::North,
+                min_difficulty: GD_HARD,
+            })),
+            map_with_actor(257).actor_at(1, 1)
+        );
+    }
+
+    #[test]
+    fn flats_fall_back_from_tile_to_area_to_level() {
+        let mut plane0 = [[AREATILE + 1; MAP_HEIGHT]; MAP_WIDTH];
+        let mut plane2 = [[0; MAP_HEIGHT]; MAP_WIDTH];
+        plane0[0][0] = 1;
+        plane0[5][5] = AREATILE + 2;
+        plane0[6][6] = AREATILE + 2;
+        plane0[9][9] = AREATILE + 3;
+        plane2[0][0] = 0x0102;
+        plane2[5][5] = 0x0304;
+        plane2[7][7] = 0x0506;
+        plane2[9][8] = 0x0700;
+        let map = Map::new(
+            plane0,
+            [[0; MAP_HEIGHT]; MAP_WIDTH],
+            plane2,
+            String::from("flats"),
+        );
+        let flats = Flats::new(&map);
+        assert_eq!((Some(6), Some(5)), flats.at(7, 7));
+        assert_eq!((Some(4), Some(3)), flats.at(6, 6));
+        assert_eq!((Some(6), Some(5)), flats.at(1, 1));
+        assert_eq!((Some(2), Some(1)), flats.at(9, 9));
+        assert_eq!((Some(6), Some(7)), flats.at(9, 8));
+    }
+
+    #[test]
+    fn decodes_statics() {
+        let kind = match map_with_actor(43).actor_at(1, 1) {
+            Some(Actor::Static(kind)) => kind,
+            other => panic!("unexpected actor {:?}", other),
+        };
+        assert_eq!(StaticKind::GoldKey, kind);
+        assert_eq!(Some(Bonus::GoldKey), kind.bonus());
+        assert_eq!(SPR_STAT_0 + 20, kind.sprite());
+        assert_eq!(StaticKind::Clip.sprite(), StaticKind::DroppedClip.sprite());
+        assert!(StaticKind::FloorLamp.is_blocking());
+        assert!(!StaticKind::CeilingLight.is_blocking());
+        assert_eq!(1, map_with_actor(43).actors(GD_BABY).count());
+        assert_eq!(
+            Some(Actor::PathTurn(Direction::NorthWest)),
+            map_with_actor(93).actor_at(1, 1)
+        );
+    }
+}
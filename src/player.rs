@@ -1,50 +1,202 @@
 use crate::constants;
-use crate::constants::{MAP_SCALE_H, MAP_SCALE_W};
+use crate::constants::{MAP_SCALE_H, MAP_SCALE_W, TICRATE};
+use crate::doors::Doors;
 use crate::map;
-use crate::map::Tile;
+use crate::map::{Bonus, Tile, GD_BABY};
+use crate::weapons::{Weapon, Weapons, MAX_AMMO};
 use std::f64::consts::PI;
 
-const ROTATE_SPEED: f64 = 0.02;
-const MOVE_SPEED: f64 = 2.5;
+/// How far the turn keys turn the player in a tic, in radians.
+pub const ROTATE_SPEED: f64 = 1.2 / TICRATE as f64;
+// Map units per tic.
+const MOVE_SPEED: f64 = 150.0 / TICRATE as f64;
 const PLAYER_WIDTH: f64 = 7.0;
+pub const MAX_HEALTH: i32 = 100;
+const START_LIVES: u32 = 3;
+const MAX_LIVES: u32 = 9;
+// Every this many points earns an extra life.
+const EXTRA_POINTS: u32 = 40000;
 
+#[derive(Debug, Copy, Clone)]
 pub enum StraightMovement {
     Forward,
     Backward,
 }
 
+#[derive(Debug, Copy, Clone)]
 pub enum SideMovement {
     StrafeRight,
     StrafeLeft,
 }
 
-pub enum TurnMovement {
-    TurnRight,
-    TurnLeft,
-}
-
 pub struct Player {
     pub x: f64,
     pub y: f64,
     pub view_angle: f64,
     pub move_angle: f64,
+    /// One bit per key, bit 0 is the gold key and bit 1 the silver one.
+    pub keys: u8,
+    pub health: i32,
+    pub lives: u32,
+    pub score: u32,
+    /// The score that earns the next extra life.
+    pub next_extra: u32,
+    /// Owned weapons and ammo.
+    pub weapons: Weapons,
+    /// Whether the player moved at running speed in the last tic.
+    pub running: bool,
 }
 
 impl Player {
+    /// A player starting a game at `x`, `y` (map units) looking along
+    /// `angle`.
+    pub fn new(x: f64, y: f64, angle: f64) -> Self {
+        Self {
+            x,
+            y,
+            view_angle: angle,
+            move_angle: angle,
+            keys: 0,
+            health: MAX_HEALTH,
+            lives: START_LIVES,
+            score: 0,
+            next_extra: EXTRA_POINTS,
+            weapons: Weapons::default(),
+            running: false,
+        }
+    }
+
+    /// Whether the player's body overlaps the given tile.
+    pub fn touches(&self, x: u8, y: u8) -> bool {
+        let (left, right) = (self.x - PLAYER_WIDTH, self.x + PLAYER_WIDTH);
+        let (top, bottom) = (self.y - PLAYER_WIDTH, self.y + PLAYER_WIDTH);
+        let (tile_x, tile_y) = (x as f64 * MAP_SCALE_W as f64, y as f64 * MAP_SCALE_H as f64);
+        right > tile_x
+            && left < tile_x + MAP_SCALE_W as f64
+            && bottom > tile_y
+            && top < tile_y + MAP_SCALE_H as f64
+    }
+
+    /// The tile the player stands in.
+    pub fn tile(&self) -> (u8, u8) {
+        (
+            (self.x / MAP_SCALE_W as f64) as u8,
+            (self.y / MAP_SCALE_H as f64) as u8,
+        )
+    }
+
+    /// The tile next to the player along the axis it is mostly looking down.
+    pub fn facing_tile(&self) -> (u8, u8) {
+        let (dx, dy) = self.facing_direction();
+        (
+            (self.x / MAP_SCALE_W as f64 + dx as f64) as u8,
+            (self.y / MAP_SCALE_H as f64 + dy as f64) as u8,
+        )
+    }
+
+    /// The unit step along the axis the player is mostly looking down.
+    pub fn facing_direction(&self) -> (i8, i8) {
+        let (sin, cos) = self.view_angle.sin_cos();
+        if sin.abs() > cos.abs() {
+            (sin.signum() as i8, 0)
+        } else {
+            (0, cos.signum() as i8)
+        }
+    }
+
+    pub fn heal(&mut self, points: i32) {
+        self.health = (self.health + points).min(MAX_HEALTH);
+    }
+
+    /// Takes damage from an enemy, a quarter of it on the easiest skill.
+    pub fn take_damage(&mut self, points: i32, difficulty: usize) {
+        let points = if difficulty == GD_BABY {
+            points / 4
+        } else {
+            points
+        };
+        self.health = (self.health - points).max(0);
+    }
+
+    /// Whether enemies have taken all of the player's health.
+    pub fn is_dead(&self) -> bool {
+        self.health == 0
+    }
+
+    /// Puts the player back at `x`, `y` after a death with one life less,
+    /// fresh health, no keys and only the pistol. The score is kept.
+    pub fn respawn(&mut self, x: f64, y: f64, angle: f64) {
+        *self = Self {
+            lives: self.lives.saturating_sub(1),
+            score: self.score,
+            next_extra: self.next_extra,
+            ..Self::new(x, y, angle)
+        };
+    }
+
+    pub fn give_extra_life(&mut self) {
+        self.lives = (self.lives + 1).min(MAX_LIVES);
+    }
+
+    /// Adds to the score, with an extra life every time it passes the next
+    /// threshold.
+    pub fn give_points(&mut self, points: u32) {
+        self.score += points;
+        while self.score >= self.next_extra {
+            self.next_extra += EXTRA_POINTS;
+            self.give_extra_life();
+        }
+    }
+
+    /// Picks up a bonus item by the original's rules. Returns false if the
+    /// player has no use for it yet, it stays where it is then.
+    pub fn pick_up(&mut self, bonus: Bonus) -> bool {
+        match bonus {
+            Bonus::GoldKey => self.keys |= 1 << 0,
+            Bonus::SilverKey => self.keys |= 1 << 1,
+            Bonus::FirstAid | Bonus::Food | Bonus::DogFood if self.health == MAX_HEALTH => {
+                return false
+            }
+            Bonus::FirstAid => self.heal(25),
+            Bonus::Food => self.heal(10),
+            Bonus::DogFood => self.heal(4),
+            // Blood and gibs only help when nearly dead.
+            Bonus::Gibs if self.health > 10 => return false,
+            Bonus::Gibs => self.heal(1),
+            Bonus::Clip | Bonus::DroppedClip if self.weapons.ammo == MAX_AMMO => return false,
+            Bonus::Clip => self.weapons.give_ammo(8),
+            Bonus::DroppedClip => self.weapons.give_ammo(4),
+            Bonus::MachineGun => self.weapons.give_weapon(Weapon::MachineGun),
+            Bonus::ChainGun => self.weapons.give_weapon(Weapon::ChainGun),
+            Bonus::Cross => self.give_points(100),
+            Bonus::Chalice => self.give_points(500),
+            Bonus::Chest => self.give_points(1000),
+            Bonus::Crown => self.give_points(5000),
+            Bonus::ExtraLife => {
+                self.heal(99);
+                self.weapons.give_ammo(25);
+                self.give_extra_life();
+            }
+        }
+        true
+    }
+
+    /// Moves the player for one tic after turning it by `turn` radians,
+    /// positive to the left.
     pub fn walk(
         &mut self,
         map: &map::Map,
+        doors: &Doors,
         straight: Option<StraightMovement>,
         side: Option<SideMovement>,
-        turn: Option<TurnMovement>,
+        turn: f64,
         run: bool,
     ) {
-        self.view_angle = match turn {
-            Some(TurnMovement::TurnLeft) => constants::norm_angle(self.view_angle + ROTATE_SPEED),
-            Some(TurnMovement::TurnRight) => constants::norm_angle(self.view_angle - ROTATE_SPEED),
-            None => self.view_angle,
-        };
+        if turn != 0.0 {
+            self.view_angle = constants::norm_angle(self.view_angle + turn);
+        }
 
+        self.running = run && (side.is_some() || straight.is_some());
         if side.is_some() || straight.is_some() {
             self.move_angle = match straight {
                 Some(StraightMovement::Forward) => match side {
@@ -80,28 +232,25 @@ impl Player {
             let collision_offset_y =
                 self.move_angle.cos().signum() * PLAYER_WIDTH / MAP_SCALE_H as f64;
 
-            let is_collision_slide_x = matches!(
-                map.tile_at(
-                    (new_map_x + collision_offset_x) as u8,
-                    (new_map_y - collision_offset_y) as u8,
-                ),
-                Tile::Wall(_)
+            let is_collision_slide_x = is_blocked(
+                map,
+                doors,
+                (new_map_x + collision_offset_x) as u8,
+                (new_map_y - collision_offset_y) as u8,
             );
 
-            let is_collision_slide_y = matches!(
-                map.tile_at(
-                    (new_map_x - collision_offset_x) as u8,
-                    (new_map_y + collision_offset_y) as u8,
-                ),
-                Tile::Wall(_)
+            let is_collision_slide_y = is_blocked(
+                map,
+                doors,
+                (new_map_x - collision_offset_x) as u8,
+                (new_map_y + collision_offset_y) as u8,
             );
 
-            let is_collision_both = matches!(
-                map.tile_at(
-                    (new_map_x + collision_offset_x) as u8,
-                    (new_map_y + collision_offset_y) as u8,
-                ),
-                Tile::Wall(_)
+            let is_collision_both = is_blocked(
+                map,
+                doors,
+                (new_map_x + collision_offset_x) as u8,
+                (new_map_y + collision_offset_y) as u8,
             );
 
             if is_collision_both && !is_collision_slide_x && !is_collision_slide_y {
@@ -137,3 +286,88 @@ impl Player {
         }
     }
 }
+
+fn is_blocked(map: &map::Map, doors: &Doors, x: u8, y: u8) -> bool {
+    match map.tile_at(x, y) {
+        Tile::Wall(_) => true,
+        Tile::Door { .. } => doors.at(x, y).is_none_or(|door| door.is_blocking()),
+        Tile::Floor => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::GD_HARD;
+
+    #[test]
+    fn health_items_are_left_until_needed() {
+        let mut player = Player::new(0.0, 0.0, 0.0);
+        assert!(!player.pick_up(Bonus::FirstAid));
+        player.take_damage(30, GD_HARD);
+        assert!(!player.pick_up(Bonus::Gibs));
+        assert!(player.pick_up(Bonus::FirstAid));
+        assert!(player.pick_up(Bonus::Food));
+        assert_eq!(MAX_HEALTH, player.health);
+
+        player.take_damage(95, GD_HARD);
+        assert!(player.pick_up(Bonus::Gibs));
+        assert_eq!(6, player.health);
+        player.take_damage(20, GD_BABY);
+        assert_eq!(1, player.health);
+    }
+
+    #[test]
+    fn respawning_costs_a_life_and_keeps_the_score() {
+        let mut player = Player::new(0.0, 0.0, 0.0);
+        player.give_points(500);
+        player.keys = 1;
+        assert!(player.pick_up(Bonus::MachineGun));
+        player.take_damage(150, GD_HARD);
+        assert!(player.is_dead());
+
+        player.respawn(96.0, 160.0, PI);
+        assert!(!player.is_dead());
+        assert_eq!(MAX_HEALTH, player.health);
+        assert_eq!(START_LIVES - 1, player.lives);
+        assert_eq!(500, player.score);
+        assert_eq!(0, player.keys);
+        assert_eq!(Weapon::Pistol, player.weapons.best);
+        assert_eq!((96.0, 160.0, PI), (player.x, player.y, player.view_angle));
+    }
+
+    #[test]
+    fn treasure_adds_up_to_extra_lives() {
+        let mut player = Player::new(0.0, 0.0, 0.0);
+        for _ in 0..8 {
+            assert!(player.pick_up(Bonus::Crown));
+        }
+        assert!(player.pick_up(Bonus::Cross));
+        assert_eq!(40100, player.score);
+        assert_eq!(START_LIVES + 1, player.lives);
+        assert_eq!(2 * EXTRA_POINTS, player.next_extra);
+
+        for _ in 0..10 {
+            player.pick_up(Bonus::ExtraLife);
+        }
+        assert_eq!(MAX_LIVES, player.lives);
+    }
+
+    #[test]
+    fn ammo_and_weapons_are_capped_and_kept() {
+        let mut player = Player::new(0.0, 0.0, 0.0);
+        assert!(player.pick_up(Bonus::MachineGun));
+        assert_eq!(Weapon::MachineGun, player.weapons.current);
+        assert!(player.pick_up(Bonus::ChainGun));
+        assert!(player.pick_up(Bonus::MachineGun));
+        assert_eq!(Weapon::ChainGun, player.weapons.best);
+
+        while player.weapons.ammo < MAX_AMMO {
+            assert!(player.pick_up(Bonus::Clip));
+        }
+        assert!(!player.pick_up(Bonus::DroppedClip));
+        assert!(player.pick_up(Bonus::GoldKey));
+        assert!(player.pick_up(Bonus::SilverKey));
+        assert_eq!(0b11, player.keys);
+    }
+}
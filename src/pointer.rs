@@ -0,0 +1,62 @@
+//! Pointer warping for mouse turning. minifb only reports where the pointer
+//! is, so to keep it from stopping at the edge of the screen it is moved
+//! back to the middle of the window after every read. Only X11 lets a client
+//! move the pointer; elsewhere `Warp::new` finds nothing to warp with.
+
+use minifb::Window;
+
+#[cfg(all(unix, not(target_os = "macos")))]
+pub use self::x11::Warp;
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+pub struct Warp;
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+impl Warp {
+    pub fn new(_window: &Window) -> Option<Warp> {
+        None
+    }
+
+    pub fn to(&self, _x: i32, _y: i32) {}
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+mod x11 {
+    use super::Window;
+    use minifb::HasRawWindowHandle;
+    use raw_window_handle::RawWindowHandle;
+    use std::os::raw::c_ulong;
+    use x11_dl::xlib::{Display, Xlib};
+
+    pub struct Warp {
+        xlib: Xlib,
+        display: *mut Display,
+        window: c_ulong,
+    }
+
+    impl Warp {
+        /// Borrows the connection of an X11 window, `None` on any other
+        /// backend or if libX11 cannot be loaded.
+        pub fn new(window: &Window) -> Option<Warp> {
+            let handle = match window.raw_window_handle() {
+                RawWindowHandle::Xlib(handle) if !handle.display.is_null() => handle,
+                _ => return None,
+            };
+            Some(Warp {
+                xlib: Xlib::open().ok()?,
+                display: handle.display as *mut Display,
+                window: handle.window,
+            })
+        }
+
+        /// Moves the pointer to `x`, `y` in window coordinates.
+        pub fn to(&self, x: i32, y: i32) {
+            // SAFETY: the display and window come from the minifb window the
+            // warp was made for, which outlives the mouse using it.
+            unsafe {
+                (self.xlib.XWarpPointer)(self.display, 0, self.window, 0, 0, 0, 0, x, y);
+                (self.xlib.XFlush)(self.display);
+            }
+        }
+    }
+}
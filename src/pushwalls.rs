@@ -0,0 +1,231 @@
+use crate::constants::*;
+use crate::map::{Actor, Map, Tile, AREATILE};
+
+// A push-wall crosses one tile every 128 tics and stops after two tiles or
+// when the next tile is blocked, the same as in the original game.
+const PUSH_SPEED: f64 = 1.0 / 128.0;
+const PUSH_TILES: u8 = 2;
+
+#[derive(Debug, Clone)]
+pub struct PushWall {
+    /// The tile the wall is sliding out of.
+    pub x: u8,
+    pub y: u8,
+    pub dx: i8,
+    pub dy: i8,
+    pub tile: u16,
+    /// How far the wall has slid towards the next tile, 0.0 to 1.0.
+    pub position: f64,
+    moved: u8,
+}
+
+impl PushWall {
+    /// Whether the wall currently overlaps the given tile.
+    pub fn covers(&self, x: u8, y: u8) -> bool {
+        (x, y) == (self.x, self.y) || Some((x, y)) == self.next()
+    }
+
+    /// Top left corner of the wall in map units.
+    pub fn origin(&self) -> (f64, f64) {
+        (
+            (self.x as f64 + self.dx as f64 * self.position) * MAP_SCALE_W as f64,
+            (self.y as f64 + self.dy as f64 * self.position) * MAP_SCALE_H as f64,
+        )
+    }
+
+    fn next(&self) -> Option<(u8, u8)> {
+        step(self.x, self.y, self.dx, self.dy)
+    }
+}
+
+pub struct PushWalls {
+    moving: Option<PushWall>,
+    pub secrets_found: u32,
+    pub secrets_total: u32,
+}
+
+impl PushWalls {
+    pub fn new(map: &Map) -> Self {
+        let secrets_total = map
+            .actors(0)
+            .filter(|(_, _, actor)| *actor == Actor::PushWall)
+            .count() as u32;
+        Self {
+            moving: None,
+            secrets_found: 0,
+            secrets_total,
+        }
+    }
+
+    pub fn moving(&self) -> Option<&PushWall> {
+        self.moving.as_ref()
+    }
+
+    /// Starts sliding the marked wall at the tile one step in the `dx`, `dy`
+    /// direction. Only one wall moves at a time, and the tile behind it must
+    /// be free, with nothing `occupied` reports standing in it. Returns false
+    /// if nothing was pushed.
+    pub fn push<F: Fn(u8, u8) -> bool>(
+        &mut self,
+        map: &mut Map,
+        x: u8,
+        y: u8,
+        dx: i8,
+        dy: i8,
+        occupied: F,
+    ) -> bool {
+        if self.moving.is_some() || map.actor_at(x, y) != Some(Actor::PushWall) {
+            return false;
+        }
+        let tile = match map.tile_at(x, y) {
+            Tile::Wall(tile) => tile,
+            _ => return false,
+        };
+        let (next_x, next_y) = match step(x, y, dx, dy) {
+            Some(next) if is_free(map, next.0, next.1) && !occupied(next.0, next.1) => next,
+            _ => return false,
+        };
+
+        map.remove_actor(x, y);
+        map.set_tile(next_x, next_y, tile);
+        self.secrets_found += 1;
+        self.moving = Some(PushWall {
+            x,
+            y,
+            dx,
+            dy,
+            tile,
+            position: 0.0,
+            moved: 0,
+        });
+        true
+    }
+
+    /// Advances the moving wall by one tic. The tiles it leaves become floor
+    /// of the area `floor` belongs to, and `occupied` tells whether something
+    /// stands in a tile, the wall stops rather than slide into it.
+    pub fn update<F: Fn(u8, u8) -> bool>(&mut self, map: &mut Map, floor: u16, occupied: F) {
+        let wall = match &mut self.moving {
+            Some(wall) => wall,
+            None => return,
+        };
+        wall.position += PUSH_SPEED;
+        if wall.position < 1.0 {
+            return;
+        }
+
+        map.set_tile(wall.x, wall.y, AREATILE + floor);
+        let (x, y) = match wall.next() {
+            Some(next) => next,
+            None => {
+                self.moving = None;
+                return;
+            }
+        };
+        wall.x = x;
+        wall.y = y;
+        wall.position = 0.0;
+        wall.moved += 1;
+
+        match wall.next() {
+            Some((nx, ny))
+                if wall.moved < PUSH_TILES && is_free(map, nx, ny) && !occupied(nx, ny) =>
+            {
+                map.set_tile(nx, ny, wall.tile);
+            }
+            _ => self.moving = None,
+        }
+    }
+}
+
+fn step(x: u8, y: u8, dx: i8, dy: i8) -> Option<(u8, u8)> {
+    let x = x
+        .checked_add_signed(dx)
+        .filter(|&x| (x as usize) < MAP_WIDTH)?;
+    let y = y
+        .checked_add_signed(dy)
+        .filter(|&y| (y as usize) < MAP_HEIGHT)?;
+    Some((x, y))
+}
+
+fn is_free(map: &Map, x: u8, y: u8) -> bool {
+    matches!(map.tile_at(x, y), Tile::Floor)
+        && !matches!(map.actor_at(x, y), Some(Actor::Static(kind)) if kind.is_blocking())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::PUSHABLETILE;
+
+    fn map_with_push_wall() -> Map {
+        let mut plane0 = [[AREATILE; MAP_HEIGHT]; MAP_WIDTH];
+        let mut plane1 = [[0; MAP_HEIGHT]; MAP_WIDTH];
+        plane0[3][4] = 12;
+        plane0[3][8] = 1;
+        plane1[3][4] = PUSHABLETILE;
+        Map::new(
+            plane0,
+            plane1,
+            [[0; MAP_HEIGHT]; MAP_WIDTH],
+            String::from("secret"),
+        )
+    }
+
+    fn tic(walls: &mut PushWalls, map: &mut Map, tics: u32) {
+        for _ in 0..tics {
+            walls.update(map, 0, |_, _| false);
+        }
+    }
+
+    #[test]
+    fn push_wall_slides_two_tiles() {
+        let mut map = map_with_push_wall();
+        let mut walls = PushWalls::new(&map);
+        assert_eq!(1, walls.secrets_total);
+        assert!(walls.push(&mut map, 3, 4, 0, 1, |_, _| false));
+        assert_eq!(1, walls.secrets_found);
+        assert!(!walls.push(&mut map, 3, 4, 0, 1, |_, _| false));
+
+        tic(&mut walls, &mut map, 64);
+        let wall = walls.moving().unwrap();
+        assert_eq!(0.5, wall.position);
+        assert!(wall.covers(3, 4) && wall.covers(3, 5));
+        assert!(matches!(map.tile_at(3, 5), Tile::Wall(12)));
+
+        tic(&mut walls, &mut map, 64);
+        assert!(matches!(map.tile_at(3, 4), Tile::Floor));
+        assert!(walls.moving().unwrap().covers(3, 6));
+
+        tic(&mut walls, &mut map, 128);
+        assert!(walls.moving().is_none());
+        assert!(matches!(map.tile_at(3, 5), Tile::Floor));
+        assert!(matches!(map.tile_at(3, 6), Tile::Wall(12)));
+        assert!(matches!(map.tile_at(3, 7), Tile::Floor));
+    }
+
+    #[test]
+    fn push_wall_needs_free_tile_behind() {
+        let mut map = map_with_push_wall();
+        map.set_tile(3, 5, 1);
+        let mut walls = PushWalls::new(&map);
+        assert!(!walls.push(&mut map, 3, 4, 0, 1, |_, _| false));
+        assert!(!walls.push(&mut map, 3, 5, 0, 1, |_, _| false));
+        assert_eq!(0, walls.secrets_found);
+    }
+
+    #[test]
+    fn push_wall_does_not_slide_onto_occupied_tile() {
+        let mut map = map_with_push_wall();
+        let mut walls = PushWalls::new(&map);
+        assert!(!walls.push(&mut map, 3, 4, 0, 1, |x, y| (x, y) == (3, 5)));
+        assert!(matches!(map.tile_at(3, 5), Tile::Floor));
+
+        assert!(walls.push(&mut map, 3, 4, 0, 1, |_, _| false));
+        for _ in 0..128 {
+            walls.update(&mut map, 0, |x, y| (x, y) == (3, 6));
+        }
+        assert!(walls.moving().is_none());
+        assert!(matches!(map.tile_at(3, 6), Tile::Floor));
+    }
+}
@@ -0,0 +1,21 @@
+/// Deterministic byte generator standing in for the original's random
+/// table, so the same inputs always play out the same way.
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u32,
+}
+
+impl Random {
+    pub fn new(seed: u32) -> Self {
+        Self { state: seed.max(1) }
+    }
+
+    /// The next value from 0 to 255.
+    pub fn byte(&mut self) -> i32 {
+        // xorshift32
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state >> 24) as i32
+    }
+}
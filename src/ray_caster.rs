@@ -1,171 +1,616 @@
 use crate::constants::*;
+use crate::doors::Doors;
 use crate::map::{Map, Tile};
 use crate::player::Player;
-use num::pow;
-use std::cmp::min;
+use crate::pushwalls::{PushWall, PushWalls};
 use std::f64::consts::PI;
 
-const PLAYER_DIAM: i32 = 6;
-const PLAYER_LEN: f64 = 40.0;
-const FIELD_OF_VIEW: f64 = PI / 2.0;
+pub const DEFAULT_FIELD_OF_VIEW: f64 = PI / 2.0;
 
-const TILE_SIZE: f64 = 4.8;
+const MIN_DISTANCE: f64 = 1.0;
 
-struct Nothing;
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Surface {
+    Wall(u16),
+    Door { lock: u16, position: f64 },
+    DoorSide,
+}
+
+/// The grid line a ray crossed to reach what it hit, `Vertical` being a line
+/// of constant x.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Side {
+    Vertical,
+    Horizontal,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hit {
+    pub x: u8,
+    pub y: u8,
+    pub side: Side,
+    /// Distance from the ray origin to the hit point, in map units.
+    pub distance: f64,
+    /// Texture column across the face that was hit, from 0.0 to 1.0.
+    pub u: f64,
+    pub surface: Surface,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum RayCast {
+    Hit(Hit),
+    /// The ray left the map without meeting anything.
+    NoHit,
+}
 
 pub struct RayHit {
+    /// Half the projected height of the wall, in pixels. Near walls are
+    /// taller than the screen.
     pub height: u32,
-    pub tile: u16,
+    pub distance: f64,
+    pub surface: Surface,
     pub horizontal: bool,
     pub tex_x: usize,
 }
 
-pub fn draw_rays(n_rays: u32, height: u32, map: &Map, player: &Player) -> Vec<RayHit> {
-    let fov_delta = FIELD_OF_VIEW / (n_rays as f64);
-    let mut hits: Vec<RayHit> = Vec::new();
-    for i in 0..n_rays {
-        let fov_angle = fov_delta * (i as f64);
+/// A pinhole camera looking through a flat projection plane `width` pixels
+/// wide. Pixels are square, so the same focal length sizes things vertically.
+#[derive(Debug, Copy, Clone)]
+pub struct Camera {
+    pub width: u32,
+    /// Horizontal field of view in radians.
+    pub fov: f64,
+}
 
-        let offset = (FIELD_OF_VIEW / 2.0 - fov_angle).atan();
-        let ray_h = cast_ray_h(map, player, offset);
-        let ray_v = cast_ray_v(map, player, offset);
-        let (hit, horiz) = match (ray_h, ray_v) {
-            ((_, _, d1, _), (_, _, d2, _)) if d1 <= d2 => (ray_h, false),
-            _ => (ray_v, true),
+impl Camera {
+    pub fn new(width: u32, fov: f64) -> Self {
+        Self { width, fov }
+    }
+
+    /// Distance from the eye to the projection plane, in pixels.
+    pub fn focal_length(&self) -> f64 {
+        self.width as f64 / 2.0 / (self.fov / 2.0).tan()
+    }
+
+    /// Angle between the view direction and the ray through the middle of a
+    /// screen column. Rays are spaced evenly on the plane, not in angle.
+    pub fn ray_offset(&self, column: u32) -> f64 {
+        let plane_x = self.width as f64 / 2.0 - (column as f64 + 0.5);
+        (plane_x / self.focal_length()).atan()
+    }
+
+    /// Half the on-screen height of a wall at a straight-ahead distance, in
+    /// pixels. The eye is halfway up the wall.
+    pub fn wall_height(&self, distance: f64) -> f64 {
+        self.focal_length() * MAP_SCALE_H as f64 / 2.0 / distance
+    }
+
+    /// Straight-ahead distance of the floor (or ceiling) seen `row` pixels
+    /// below (or above) the horizon, the inverse of `wall_height`.
+    pub fn row_distance(&self, row: f64) -> f64 {
+        self.focal_length() * MAP_SCALE_H as f64 / 2.0 / row
+    }
+}
+
+pub fn draw_rays(
+    camera: &Camera,
+    map: &Map,
+    doors: &Doors,
+    pushwalls: &PushWalls,
+    player: &Player,
+) -> Vec<Option<RayHit>> {
+    let mut hits: Vec<Option<RayHit>> = Vec::new();
+    for i in 0..camera.width {
+        let offset = camera.ray_offset(i);
+        let angle = player.view_angle + offset;
+        let hit = match cast_ray(map, doors, pushwalls, player.x, player.y, angle) {
+            RayCast::Hit(hit) => hit,
+            RayCast::NoHit => {
+                hits.push(None);
+                continue;
+            }
         };
-        let (_, _, distance, tile) = hit;
-
-        let adj_distance = distance * offset.cos();
-        let ray_height = TILE_SIZE * n_rays as f64 / adj_distance;
-        let tex_x = ray_to_tex_coordinatinates(hit.0, hit.1, horiz);
-        hits.push(RayHit {
-            height: min(height, ray_height as u32),
-            tile,
-            horizontal: horiz,
-            tex_x,
-        });
+
+        let adj_distance = hit.distance * offset.cos();
+        let ray_height = camera.wall_height(adj_distance);
+        hits.push(Some(RayHit {
+            height: ray_height as u32,
+            distance: adj_distance,
+            surface: hit.surface,
+            horizontal: hit.side == Side::Horizontal,
+            tex_x: ((hit.u * WALLPIC_WIDTH as f64) as usize).min(WALLPIC_WIDTH - 1),
+        }));
     }
     hits
 }
 
-fn cast_ray_v(map: &Map, player: &Player, ray_offset: f64) -> (f64, f64, f64, u16) {
-    let ray_angle = norm_angle(player.view_angle + ray_offset);
+pub struct Projection {
+    pub column: f64,
+    pub width: f64,
+    pub height: f64,
+    pub distance: f64,
+}
+
+pub fn project(camera: &Camera, player: &Player, x: f64, y: f64) -> Option<Projection> {
+    let (sin, cos) = player.view_angle.sin_cos();
+    let (dx, dy) = (x - player.x, y - player.y);
+    let forward = dx * sin + dy * cos;
+    let lateral = dx * cos - dy * sin;
 
-    if ray_angle == ANGLE_LEFT || ray_angle == ANGLE_RIGHT {
-        return (0.0, 0.0, f64::INFINITY, 0);
+    if forward < MIN_DISTANCE {
+        return None;
     }
 
-    let (rx, ry, xo, yo) = if !(ANGLE_RIGHT..=ANGLE_LEFT).contains(&ray_angle) {
-        let round_y = ctrunc(player.y, MAP_SCALE_H, 1.0);
-        let a = round_y - player.y;
-        let b = a * ray_angle.tan();
-        let c = MAP_SCALE_H as f64 * ray_angle.tan();
-        (player.x + b, round_y, c, MAP_SCALE_H as f64)
-    } else {
-        let round_y = ctrunc(player.y, MAP_SCALE_H, 0.0);
-        let a = player.y - round_y;
-        let b = a * ray_angle.tan();
-        let c = MAP_SCALE_H as f64 * ray_angle.tan();
-        (
-            player.x - b,
-            round_y - 0.000001,
-            -1.0 * c,
-            -1.0 * MAP_SCALE_H as f64,
-        )
-    };
-    follow_ray(map, player, rx, ry, xo, yo)
+    let focal_length = camera.focal_length();
+    Some(Projection {
+        column: camera.width as f64 / 2.0 - lateral / forward * focal_length,
+        width: MAP_SCALE_W as f64 / forward * focal_length,
+        height: camera.wall_height(forward),
+        distance: forward,
+    })
+}
+
+/// Ray state while walking the grid, in tile units. `t` is the distance
+/// along the ray, in tiles, at which the current cell was entered.
+struct Ray {
+    x: f64,
+    y: f64,
+    dir_x: f64,
+    dir_y: f64,
 }
 
-fn cast_ray_h(map: &Map, player: &Player, ray_offset: f64) -> (f64, f64, f64, u16) {
-    let ray_angle = norm_angle(player.view_angle + ray_offset);
+impl Ray {
+    fn at(&self, t: f64) -> (f64, f64) {
+        (self.x + t * self.dir_x, self.y + t * self.dir_y)
+    }
 
-    if ray_angle == ANGLE_UP || ray_angle == ANGLE_DOWN {
-        return (0.0, 0.0, f64::INFINITY, 0);
+    // Distance along the ray to the line `x = line` (or `y = line`), infinite
+    // if the ray runs parallel to it.
+    fn reach(&self, side: Side, line: f64) -> f64 {
+        let (start, dir) = match side {
+            Side::Vertical => (self.x, self.dir_x),
+            Side::Horizontal => (self.y, self.dir_y),
+        };
+        if dir == 0.0 {
+            f64::INFINITY
+        } else {
+            (line - start) / dir
+        }
     }
 
-    let (rx, ry, xo, yo) = if ray_angle < ANGLE_UP {
-        let round_x = ctrunc(player.x, MAP_SCALE_W, 1.0);
-        let b = round_x - player.x;
-        let a = b / ray_angle.tan();
-        let c = MAP_SCALE_W as f64 / ray_angle.tan();
-        (round_x, player.y + a, MAP_SCALE_W as f64, c)
-    } else {
-        let round_x = ctrunc(player.x, MAP_SCALE_W, 0.0);
-        let b = player.x - round_x;
-        let a = b / ray_angle.tan();
-        let c = MAP_SCALE_W as f64 / ray_angle.tan();
-        (
-            round_x - 0.00001,
-            player.y - a,
-            -1.0 * MAP_SCALE_W as f64,
-            -1.0 * c,
-        )
-    };
-    follow_ray(map, player, rx, ry, xo, yo)
+    fn hit(&self, cell: (i32, i32), side: Side, t: f64, u: f64, surface: Surface) -> RayCast {
+        let (dx, dy) = (
+            t * self.dir_x * MAP_SCALE_W as f64,
+            t * self.dir_y * MAP_SCALE_H as f64,
+        );
+        RayCast::Hit(Hit {
+            x: cell.0 as u8,
+            y: cell.1 as u8,
+            side,
+            distance: dx.hypot(dy),
+            u: u.clamp(0.0, 1.0),
+            surface,
+        })
+    }
 }
 
-fn follow_ray(
+/// Walks the map grid cell by cell from `x`, `y` (map units) along `angle`
+/// and returns the first wall, door or moving push-wall face the ray meets.
+pub fn cast_ray(
     map: &Map,
-    player: &Player,
+    doors: &Doors,
+    pushwalls: &PushWalls,
     x: f64,
     y: f64,
-    xo: f64,
-    yo: f64,
-) -> (f64, f64, f64, u16) {
-    let (mut rx, mut ry) = (x, y);
-    for _ in 1..MAP_HEIGHT {
-        match read_map(map, rx, ry) {
-            Ok(Tile::Wall(tile)) => {
-                return (rx, ry, distance(player, rx, ry), tile);
+    angle: f64,
+) -> RayCast {
+    let (dir_x, dir_y) = angle.sin_cos();
+    let ray = Ray {
+        x: x / MAP_SCALE_W as f64,
+        y: y / MAP_SCALE_H as f64,
+        dir_x,
+        dir_y,
+    };
+    let step_x = if dir_x < 0.0 { -1 } else { 1 };
+    let step_y = if dir_y < 0.0 { -1 } else { 1 };
+    let mut cell = (ray.x.floor() as i32, ray.y.floor() as i32);
+    let mut next_x = ray.reach(Side::Vertical, (cell.0 + (step_x + 1) / 2) as f64);
+    let mut next_y = ray.reach(Side::Horizontal, (cell.1 + (step_y + 1) / 2) as f64);
+    let delta_x = ray.reach(Side::Vertical, ray.x + step_x as f64);
+    let delta_y = ray.reach(Side::Horizontal, ray.y + step_y as f64);
+
+    let mut t = 0.0;
+    let mut side = Side::Vertical;
+    let mut previous: Option<Tile> = None;
+    let pushwall = pushwalls.moving();
+
+    while let Some(tile) = tile_at(map, cell) {
+        let exit = next_x.min(next_y);
+        match tile {
+            Tile::Wall(_) if pushwall.is_some_and(|wall| covers(wall, cell)) => {}
+            Tile::Wall(tile) if previous.is_some() => {
+                let surface = match previous {
+                    Some(Tile::Door { .. }) => Surface::DoorSide,
+                    _ => Surface::Wall(tile),
+                };
+                let u = wall_u(&ray, side, t, cell, step_x, step_y);
+                return ray.hit(cell, side, t, u, surface);
             }
-            Err(_) => {
-                return (rx, ry, distance(player, rx, ry), 0);
+            Tile::Door { vertical, lock } => {
+                // Doors sit halfway into their cell and slide along it, the
+                // part that has already gone into the wall lets rays pass.
+                let side = if vertical {
+                    Side::Vertical
+                } else {
+                    Side::Horizontal
+                };
+                let (line, along_start) = match side {
+                    Side::Vertical => (cell.0 as f64 + 0.5, cell.1),
+                    Side::Horizontal => (cell.1 as f64 + 0.5, cell.0),
+                };
+                let door_t = ray.reach(side, line);
+                let position = doors
+                    .at(cell.0 as u8, cell.1 as u8)
+                    .map_or(0.0, |door| door.position);
+                let (hx, hy) = ray.at(door_t);
+                let along = match side {
+                    Side::Vertical => hy,
+                    Side::Horizontal => hx,
+                } - along_start as f64;
+                if (t..=exit).contains(&door_t) && (position..1.0).contains(&along) {
+                    let surface = Surface::Door { lock, position };
+                    return ray.hit(cell, side, door_t, along - position, surface);
+                }
             }
             _ => {}
         }
-        rx += xo;
-        ry += yo;
+
+        if let Some(wall) = pushwall.filter(|wall| covers(wall, cell)) {
+            if let Some((wall_t, wall_side, u)) = push_wall_hit(&ray, wall, step_x, step_y) {
+                if (t..=exit).contains(&wall_t) {
+                    return ray.hit(cell, wall_side, wall_t, u, Surface::Wall(wall.tile));
+                }
+            }
+        }
+
+        previous = Some(tile);
+        if next_x < next_y {
+            t = next_x;
+            next_x += delta_x;
+            cell.0 += step_x;
+            side = Side::Vertical;
+        } else {
+            t = next_y;
+            next_y += delta_y;
+            cell.1 += step_y;
+            side = Side::Horizontal;
+        }
     }
 
-    (rx, ry, distance(player, rx, ry), 0)
+    RayCast::NoHit
 }
 
-fn read_map(map: &Map, x: f64, y: f64) -> Result<Tile, Nothing> {
-    let mx = cdiv(x, MAP_SCALE_W, 0.0);
-    let my = cdiv(y, MAP_SCALE_H, 0.0);
-    if mx >= MAP_WIDTH || my >= MAP_HEIGHT {
-        Err(Nothing)
-    } else {
-        Ok(map.tile_at(mx as u8, my as u8))
+/// Like `cast_ray` from `origin` (map units), but only reports what is hit
+/// within `max_distance`.
+pub fn raycast(
+    map: &Map,
+    doors: &Doors,
+    pushwalls: &PushWalls,
+    origin: (f64, f64),
+    angle: f64,
+    max_distance: f64,
+) -> RayCast {
+    match cast_ray(map, doors, pushwalls, origin.0, origin.1, angle) {
+        RayCast::Hit(hit) if hit.distance <= max_distance => RayCast::Hit(hit),
+        _ => RayCast::NoHit,
+    }
+}
+
+/// The first wall, door or moving push-wall on the straight line from
+/// `from` to `to`, if anything is in between.
+pub fn trace(
+    map: &Map,
+    doors: &Doors,
+    pushwalls: &PushWalls,
+    from: (f64, f64),
+    to: (f64, f64),
+) -> Option<Hit> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let distance = dx.hypot(dy);
+    match raycast(map, doors, pushwalls, from, dx.atan2(dy), distance) {
+        RayCast::Hit(hit) if hit.distance < distance => Some(hit),
+        _ => None,
     }
 }
 
-fn ray_to_tex_coordinatinates(rx: f64, ry: f64, horizontal: bool) -> usize {
-    let tx = (rx / MAP_SCALE_W as f64).fract();
-    let ty = (ry / MAP_SCALE_H as f64).fract();
+/// Whether `a` and `b` can see each other. Closed doors block the view,
+/// and a door that is partly open only lets it through the gap.
+pub fn visible(
+    map: &Map,
+    doors: &Doors,
+    pushwalls: &PushWalls,
+    a: (f64, f64),
+    b: (f64, f64),
+) -> bool {
+    trace(map, doors, pushwalls, a, b).is_none()
+}
 
-    let fract = if horizontal {
-        if ty < 0.5 {
-            1.0 - tx
-        } else {
-            tx
+fn tile_at(map: &Map, (x, y): (i32, i32)) -> Option<Tile> {
+    let inside = (0..MAP_WIDTH as i32).contains(&x) && (0..MAP_HEIGHT as i32).contains(&y);
+    inside.then(|| map.tile_at(x as u8, y as u8))
+}
+
+fn covers(wall: &PushWall, (x, y): (i32, i32)) -> bool {
+    wall.covers(x as u8, y as u8)
+}
+
+// Texture columns run left to right as seen from the side the ray arrives
+// on, so faces looking down the negative axes are mirrored.
+fn face_u(side: Side, along: f64, step_x: i32, step_y: i32) -> f64 {
+    match side {
+        Side::Vertical if step_x > 0 => along,
+        Side::Vertical => 1.0 - along,
+        Side::Horizontal if step_y > 0 => 1.0 - along,
+        Side::Horizontal => along,
+    }
+}
+
+fn wall_u(ray: &Ray, side: Side, t: f64, cell: (i32, i32), step_x: i32, step_y: i32) -> f64 {
+    let (hx, hy) = ray.at(t);
+    let along = match side {
+        Side::Vertical => hy - cell.1 as f64,
+        Side::Horizontal => hx - cell.0 as f64,
+    };
+    face_u(side, along, step_x, step_y)
+}
+
+// A moving push-wall is a one tile box between grid lines. Returns where the
+// ray enters it, through which side, and the texture column there.
+fn push_wall_hit(ray: &Ray, wall: &PushWall, step_x: i32, step_y: i32) -> Option<(f64, Side, f64)> {
+    let (left, top) = wall.origin();
+    let (left, top) = (left / MAP_SCALE_W as f64, top / MAP_SCALE_H as f64);
+    let slab = |side: Side, start: f64, low: f64| {
+        let (a, b) = (ray.reach(side, low), ray.reach(side, low + 1.0));
+        if a.is_infinite() {
+            // Parallel to this pair of faces, inside or outside for good.
+            return (low..low + 1.0)
+                .contains(&start)
+                .then_some((f64::NEG_INFINITY, f64::INFINITY));
         }
-    } else if tx < 0.5 {
-        ty
+        Some((a.min(b), a.max(b)))
+    };
+    let (near_x, far_x) = slab(Side::Vertical, ray.x, left)?;
+    let (near_y, far_y) = slab(Side::Horizontal, ray.y, top)?;
+    let (near, far) = (near_x.max(near_y), far_x.min(far_y));
+    if near > far || far < 0.0 {
+        return None;
+    }
+
+    let side = if near_x > near_y {
+        Side::Vertical
     } else {
-        1.0 - ty
+        Side::Horizontal
     };
-    (fract * WALLPIC_WIDTH as f64) as usize
+    let (hx, hy) = ray.at(near);
+    let along = match side {
+        Side::Vertical => hy - top,
+        Side::Horizontal => hx - left,
+    };
+    Some((near, side, face_u(side, along, step_x, step_y)))
 }
 
-fn cdiv(x: f64, scale: u32, updown: f64) -> usize {
-    (x / scale as f64 + updown).trunc() as usize
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::AREATILE;
+    use proptest::prelude::*;
 
-fn ctrunc(x: f64, scale: u32, updown: f64) -> f64 {
-    (x / scale as f64 + updown).trunc() * scale as f64
-}
+    const WALL: u16 = 1;
+
+    fn map_from(plane0: [[u16; MAP_HEIGHT]; MAP_WIDTH]) -> Map {
+        let empty = [[0; MAP_HEIGHT]; MAP_WIDTH];
+        Map::new(plane0, empty, empty, String::from("rays"))
+    }
+
+    // Random walls with a solid border and a free cell in the middle.
+    fn random_map(seed: u64, density: u64) -> Map {
+        let mut state = seed | 1;
+        let mut plane0 = [[AREATILE; MAP_HEIGHT]; MAP_WIDTH];
+        for (x, column) in plane0.iter_mut().enumerate() {
+            for (y, code) in column.iter_mut().enumerate() {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                let border = x == 0 || y == 0 || x == MAP_WIDTH - 1 || y == MAP_HEIGHT - 1;
+                if border || state % 100 < density {
+                    *code = WALL;
+                }
+            }
+        }
+        plane0[32][32] = AREATILE;
+        map_from(plane0)
+    }
+
+    // Where the ray enters the box of a cell, in map units, ignoring cells
+    // it starts in or does not touch.
+    fn entry(x: f64, y: f64, angle: f64, cell: (usize, usize)) -> Option<f64> {
+        let (dir_x, dir_y) = angle.sin_cos();
+        let (ox, oy) = (x / MAP_SCALE_W as f64, y / MAP_SCALE_H as f64);
+        let slab = |start: f64, dir: f64, low: f64| {
+            let (a, b) = ((low - start) / dir, (low + 1.0 - start) / dir);
+            (a.min(b), a.max(b))
+        };
+        let (near_x, far_x) = slab(ox, dir_x, cell.0 as f64);
+        let (near_y, far_y) = slab(oy, dir_y, cell.1 as f64);
+        let (near, far) = (near_x.max(near_y), far_x.min(far_y));
+        (near <= far && near > 0.0).then_some(near * MAP_SCALE_W as f64)
+    }
+
+    // Tests the ray against every wall in the map and keeps the nearest.
+    fn brute_force(map: &Map, x: f64, y: f64, angle: f64) -> Option<f64> {
+        (0..MAP_WIDTH)
+            .flat_map(|cx| (0..MAP_HEIGHT).map(move |cy| (cx, cy)))
+            .filter(|&(cx, cy)| matches!(map.tile_at(cx as u8, cy as u8), Tile::Wall(_)))
+            .filter_map(|cell| entry(x, y, angle, cell))
+            .min_by(f64::total_cmp)
+    }
+
+    fn cast(map: &Map, x: f64, y: f64, angle: f64) -> RayCast {
+        cast_ray(map, &Doors::new(map), &PushWalls::new(map), x, y, angle)
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(500))]
 
-fn distance(player: &Player, x: f64, y: f64) -> f64 {
-    (pow(x - player.x, 2) + pow(y - player.y, 2)).sqrt()
+        #[test]
+        fn agrees_with_brute_force(
+            seed: u64,
+            density in 0u64..40,
+            fx in 0.01f64..0.99,
+            fy in 0.01f64..0.99,
+            angle in 0.0..2.0 * PI,
+        ) {
+            let map = random_map(seed, density);
+            let x = (32.0 + fx) * MAP_SCALE_W as f64;
+            let y = (32.0 + fy) * MAP_SCALE_H as f64;
+            let distance = brute_force(&map, x, y, angle).unwrap();
+            let hit = match cast(&map, x, y, angle) {
+                RayCast::Hit(hit) => hit,
+                RayCast::NoHit => panic!("ray escaped a walled map"),
+            };
+
+            prop_assert!((hit.distance - distance).abs() < 1e-9);
+            prop_assert!(matches!(map.tile_at(hit.x, hit.y), Tile::Wall(_)));
+            prop_assert!((0.0..=1.0).contains(&hit.u));
+            // Rays grazing a corner may report either wall meeting there, as
+            // long as the ray enters that one at the same distance.
+            let cell_entry = entry(x, y, angle, (hit.x as usize, hit.y as usize));
+            prop_assert!(cell_entry.is_some_and(|entry| (entry - distance).abs() < 1e-9));
+        }
+    }
+
+    #[test]
+    fn axis_aligned_rays_hit_exact_faces() {
+        let map = random_map(0, 0);
+        let (x, y) = (32.5 * MAP_SCALE_W as f64, 32.25 * MAP_SCALE_H as f64);
+        let hit = match cast(&map, x, y, ANGLE_RIGHT) {
+            RayCast::Hit(hit) => hit,
+            RayCast::NoHit => panic!("no hit"),
+        };
+        assert_eq!((63, 32, Side::Vertical), (hit.x, hit.y, hit.side));
+        assert_eq!(30.5 * MAP_SCALE_W as f64, hit.distance);
+        assert_eq!(0.25, hit.u);
+
+        let hit = match cast(&map, x, y, ANGLE_UP) {
+            RayCast::Hit(hit) => hit,
+            RayCast::NoHit => panic!("no hit"),
+        };
+        assert_eq!((32, 0, Side::Horizontal), (hit.x, hit.y, hit.side));
+        assert_eq!(31.25 * MAP_SCALE_H as f64, hit.distance);
+    }
+
+    #[test]
+    fn open_map_is_no_hit() {
+        let map = map_from([[AREATILE; MAP_HEIGHT]; MAP_WIDTH]);
+        assert_eq!(RayCast::NoHit, cast(&map, 100.0, 100.0, 1.0));
+    }
+
+    #[test]
+    fn closed_door_is_hit_in_the_middle_with_jambs_beside_it() {
+        let mut plane0 = [[AREATILE; MAP_HEIGHT]; MAP_WIDTH];
+        plane0[10][5] = 90;
+        plane0[11][5] = WALL;
+        plane0[10][4] = WALL;
+        let map = map_from(plane0);
+        let y = 5.5 * MAP_SCALE_H as f64;
+
+        let hit = match cast(&map, 8.5 * MAP_SCALE_W as f64, y, ANGLE_RIGHT) {
+            RayCast::Hit(hit) => hit,
+            RayCast::NoHit => panic!("no hit"),
+        };
+        assert_eq!((10, 5, Side::Vertical), (hit.x, hit.y, hit.side));
+        assert_eq!(2.0 * MAP_SCALE_W as f64, hit.distance);
+        assert!(matches!(hit.surface, Surface::Door { lock: 0, .. }));
+
+        // Looking up from inside the door cell sees the jamb.
+        let hit = match cast(&map, 10.25 * MAP_SCALE_W as f64, y, ANGLE_UP) {
+            RayCast::Hit(hit) => hit,
+            RayCast::NoHit => panic!("no hit"),
+        };
+        assert_eq!((10, 4, Surface::DoorSide), (hit.x, hit.y, hit.surface));
+    }
+
+    #[test]
+    fn queries_stop_at_the_first_blocking_cell() {
+        let mut plane0 = [[AREATILE; MAP_HEIGHT]; MAP_WIDTH];
+        plane0[10][5] = WALL;
+        plane0[12][5] = WALL;
+        let map = map_from(plane0);
+        let (doors, pushwalls) = (Doors::new(&map), PushWalls::new(&map));
+        let at = |x: f64, y: f64| (x * MAP_SCALE_W as f64, y * MAP_SCALE_H as f64);
+
+        let hit = trace(&map, &doors, &pushwalls, at(8.5, 5.5), at(14.5, 5.5)).unwrap();
+        assert_eq!((10, 5), (hit.x, hit.y));
+        assert!(visible(
+            &map,
+            &doors,
+            &pushwalls,
+            at(8.5, 5.5),
+            at(9.5, 5.5)
+        ));
+        assert!(visible(
+            &map,
+            &doors,
+            &pushwalls,
+            at(8.5, 5.5),
+            at(8.5, 1.5)
+        ));
+
+        let short = raycast(&map, &doors, &pushwalls, at(8.5, 5.5), ANGLE_RIGHT, 16.0);
+        assert_eq!(RayCast::NoHit, short);
+        let long = raycast(&map, &doors, &pushwalls, at(8.5, 5.5), ANGLE_RIGHT, 32.0);
+        assert!(matches!(long, RayCast::Hit(Hit { x: 10, y: 5, .. })));
+    }
+
+    #[test]
+    fn doors_block_sight_until_open() {
+        let mut plane0 = [[AREATILE; MAP_HEIGHT]; MAP_WIDTH];
+        plane0[10][5] = 90;
+        let map = map_from(plane0);
+        let (mut doors, pushwalls) = (Doors::new(&map), PushWalls::new(&map));
+        let at = |x: f64, y: f64| (x * MAP_SCALE_W as f64, y * MAP_SCALE_H as f64);
+        let (a, b) = (at(8.5, 5.25), at(12.5, 5.25));
+        assert!(!visible(&map, &doors, &pushwalls, a, b));
+
+        // Half open, the line through the top half of the cell gets through.
+        doors.operate(10, 5, 0);
+        for _ in 0..32 {
+            doors.update(|_, _| false);
+        }
+        assert!(visible(&map, &doors, &pushwalls, a, b));
+        let (a, b) = (at(8.5, 5.75), at(12.5, 5.75));
+        assert!(!visible(&map, &doors, &pushwalls, a, b));
+    }
+
+    #[test]
+    fn camera_spaces_rays_evenly_and_keeps_walls_square() {
+        let camera = Camera::new(320, 100f64.to_radians());
+        let plane: Vec<f64> = (0..camera.width)
+            .map(|column| camera.ray_offset(column).tan() * camera.focal_length())
+            .collect();
+        for pair in plane.windows(2) {
+            assert!((pair[0] - pair[1] - 1.0).abs() < 1e-9);
+        }
+        assert!((plane[0] + plane[319]).abs() < 1e-9);
+        assert!((camera.ray_offset(0) - camera.fov / 2.0).abs() < camera.fov / 320.0);
+
+        let distance = 3.0 * MAP_SCALE_W as f64;
+        let height = camera.wall_height(distance);
+        assert!((camera.row_distance(height) - distance).abs() < 1e-9);
+
+        let player = Player::new(0.0, 0.0, ANGLE_DOWN);
+        let projection = project(&camera, &player, 0.0, distance).unwrap();
+        assert_eq!(160.0, projection.column);
+        assert!((projection.width - 2.0 * projection.height).abs() < 1e-9);
+    }
 }
@@ -0,0 +1,975 @@
+use crate::cache::{self, CompShape, Picture};
+use crate::constants::*;
+use crate::game::Game;
+use crate::map::{Map, Tile};
+use crate::player::Player;
+use crate::ray_caster::{self, Camera, Surface};
+use crate::sprites;
+use core::slice::Iter;
+
+type ColorMap = [(u8, u8, u8); 256];
+
+const VGA_FLOOR_COLOR: usize = 0x19;
+const VGA_CEILING_COLORS: [usize; 60] = [
+    0x1d, 0x1d, 0x1d, 0x1d, 0x1d, 0x1d, 0x1d, 0x1d, 0x1d, 0xbf, 0x4e, 0x4e, 0x4e, 0x1d, 0x8d, 0x4e,
+    0x1d, 0x2d, 0x1d, 0x8d, 0x1d, 0x1d, 0x1d, 0x1d, 0x1d, 0x2d, 0xdd, 0x1d, 0x1d, 0x98, 0x1d, 0x9d,
+    0x2d, 0xdd, 0xdd, 0x9d, 0x2d, 0x4d, 0x1d, 0xdd, 0x7d, 0x1d, 0x2d, 0x2d, 0xdd, 0xd7, 0x1d, 0x1d,
+    0x1d, 0x2d, 0x1d, 0x1d, 0x1d, 0x1d, 0xdd, 0xdd, 0x7d, 0xdd, 0xdd, 0xdd,
+];
+
+const DARKNESS: f64 = 0.75;
+const ELEVATOR_LOCK: u16 = 5;
+
+/// Software renderer drawing into a plain framebuffer of `0x00RRGGBB`
+/// pixels, with no window attached.
+pub struct Renderer {
+    pub width: u32,
+    pub height: u32,
+    pub pix_width: u32,
+    pub pix_height: u32,
+    pub pix_center: u32,
+    pub scale: u32,
+    pub color_map: ColorMap,
+    pub buffer: Vec<u32>,
+    /// Texture the floor and ceiling instead of filling them with flat colors.
+    pub textured_floors: bool,
+    /// Horizontal field of view in radians.
+    pub fov: f64,
+    pub show_minimap: bool,
+}
+
+impl Renderer {
+    pub fn new(width: u32, height: u32) -> Self {
+        let mut renderer = Self {
+            scale: 1,
+            width: 0,
+            height: 0,
+            pix_width: 0,
+            pix_height: 0,
+            pix_center: 0,
+            color_map: build_color_map(),
+            buffer: Vec::new(),
+            textured_floors: false,
+            fov: ray_caster::DEFAULT_FIELD_OF_VIEW,
+            show_minimap: true,
+        };
+        renderer.resize(width, height);
+        renderer
+    }
+
+    /// Reallocates the framebuffer for a new size and lays the view out in
+    /// it. `scale` becomes the largest whole multiple of the original
+    /// 320x200 screen that fits, for art drawn at its native resolution.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.scale = (width / BASE_WIDTH).min(height / BASE_HEIGHT).max(1);
+        self.width = width;
+        self.height = height;
+        self.pix_width = width;
+        self.pix_height = height.saturating_sub(STATUS_LINES * self.scale);
+        self.pix_center = self.pix_height / 2;
+        self.buffer = vec![0; width as usize * height as usize];
+    }
+
+    /// Pixel size of the overlays (minimap, frame counter), growing slower
+    /// than `scale` so they stay out of the way on big screens.
+    pub fn hud_scale(&self) -> u32 {
+        self.scale.div_ceil(2)
+    }
+
+    /// The camera the 3D view is projected through.
+    pub fn camera(&self) -> Camera {
+        Camera::new(self.pix_width, self.fov)
+    }
+
+    /// Draws a whole frame of the game as seen by the player.
+    pub fn render(&mut self, game: &Game) {
+        let zbuffer = self.draw_world(game);
+        self.draw_sprites(game, &zbuffer);
+        self.draw_weapon(game);
+        if self.show_minimap {
+            self.draw_minimap(&game.map, &game.player, self.hud_scale());
+        }
+    }
+
+    pub fn draw_world(&mut self, game: &Game) -> Vec<f64> {
+        let ray_hits = ray_caster::draw_rays(
+            &self.camera(),
+            &game.map,
+            &game.doors,
+            &game.pushwalls,
+            &game.player,
+        );
+
+        // Textured floors leave the flat colors wherever they find no texture.
+        self.draw_flat_colors(game);
+        if self.textured_floors {
+            self.draw_flats(game);
+        }
+
+        for x in 0..self.pix_width {
+            let hit = match &ray_hits[x as usize] {
+                Some(hit) => hit,
+                None => continue,
+            };
+
+            let face = if hit.horizontal { 0 } else { 1 };
+            let texture = match wall_page(hit.surface, game.cache.door_wall(), face)
+                .and_then(|wallpic| game.cache.get_texture(wallpic).ok())
+            {
+                Some(texture) => texture,
+                None => continue,
+            };
+
+            let current = hit.height as i64;
+            let xoff = hit.tex_x * WALLPIC_WIDTH;
+
+            // Walls taller than the view are clipped to it, the texture
+            // starting as far down as the rows cut off at the top.
+            let top = self.pix_center as i64 - current;
+            let start = top.max(0);
+            let end = (self.pix_center as i64 + current).min(self.pix_height as i64);
+            let step = WALLPIC_WIDTH as f64 / 2.0 / current as f64;
+            let mut ytex = (start - top) as f64 * step;
+
+            for y in start..end {
+                let source = (ytex as usize).min(WALLPIC_WIDTH - 1) + xoff;
+                let color_index = texture[source] as usize;
+
+                self.put_darkened_pixel(x, y as u32, color_index, hit.height);
+
+                ytex += step;
+            }
+        }
+
+        ray_hits
+            .iter()
+            .map(|hit| hit.as_ref().map_or(f64::INFINITY, |hit| hit.distance))
+            .collect()
+    }
+
+    fn draw_flat_colors(&mut self, game: &Game) {
+        for x in 0..self.pix_width {
+            for y in 0..self.pix_height / 2 {
+                self.put_darkened_pixel(
+                    x,
+                    y,
+                    VGA_CEILING_COLORS[game.episode * cache::LEVELS_PER_EPISODE + game.level],
+                    self.pix_center - y,
+                );
+            }
+            for y in self.pix_height / 2..self.pix_height {
+                self.put_darkened_pixel(x, y, VGA_FLOOR_COLOR, y - self.pix_center);
+            }
+        }
+    }
+
+    /// Casts every floor and ceiling row back onto the map and samples the
+    /// texture of the tile it lands in. Halves no plane-2 entry paints keep
+    /// the flat colors. Walls are drawn over this afterwards.
+    fn draw_flats(&mut self, game: &Game) {
+        let player = &game.player;
+        let camera = self.camera();
+        let rays: Vec<(f64, f64)> = (0..self.pix_width)
+            .map(|x| {
+                let offset = camera.ray_offset(x);
+                let (sin, cos) = (player.view_angle + offset).sin_cos();
+                (sin / offset.cos(), cos / offset.cos())
+            })
+            .collect();
+
+        for row in 0..self.pix_center {
+            let distance = camera.row_distance(row as f64 + 0.5);
+            for (x, (dx, dy)) in rays.iter().enumerate() {
+                let tile_x = (player.x + distance * dx) / MAP_SCALE_W as f64;
+                let tile_y = (player.y + distance * dy) / MAP_SCALE_H as f64;
+                if tile_x < 0.0
+                    || tile_y < 0.0
+                    || tile_x >= MAP_WIDTH as f64
+                    || tile_y >= MAP_HEIGHT as f64
+                {
+                    continue;
+                }
+
+                let (floor, ceiling) = game.flats.at(tile_x as u8, tile_y as u8);
+                let flat_texture = |wall: u16| {
+                    let page = (wall as usize).checked_sub(1)? * 2;
+                    game.cache.get_texture(page).ok()
+                };
+                let source = (tile_x.fract() * WALLPIC_WIDTH as f64) as usize * WALLPIC_WIDTH
+                    + (tile_y.fract() * WALLPIC_WIDTH as f64) as usize;
+                let x = x as u32;
+
+                if let Some(texture) = floor.and_then(flat_texture) {
+                    self.put_darkened_pixel(
+                        x,
+                        self.pix_center + row,
+                        texture[source] as usize,
+                        row,
+                    );
+                }
+                if let Some(texture) = ceiling.and_then(flat_texture) {
+                    let y = self.pix_center - 1 - row;
+                    self.put_darkened_pixel(x, y, texture[source] as usize, row + 1);
+                }
+            }
+        }
+    }
+
+    pub fn draw_sprites(&mut self, game: &Game, zbuffer: &[f64]) {
+        let player = &game.player;
+        let camera = self.camera();
+        let mut visible = Vec::new();
+
+        for (x, y, actor) in &game.actors {
+            let sprite_x = MAP_SCALE_W as f64 * (*x as f64 + 0.5);
+            let sprite_y = MAP_SCALE_H as f64 * (*y as f64 + 0.5);
+            let projection = match ray_caster::project(&camera, player, sprite_x, sprite_y) {
+                Some(projection) => projection,
+                None => continue,
+            };
+            let view_angle = (sprite_x - player.x).atan2(sprite_y - player.y);
+            if let Some(sprite) = sprites::actor_sprite(actor, view_angle) {
+                visible.push((projection, sprite));
+            }
+        }
+        for enemy in &game.enemies {
+            let projection = match ray_caster::project(&camera, player, enemy.x, enemy.y) {
+                Some(projection) => projection,
+                None => continue,
+            };
+            let view_angle = (enemy.x - player.x).atan2(enemy.y - player.y);
+            if let Some(sprite) = enemy.sprite(view_angle) {
+                visible.push((projection, sprite));
+            }
+        }
+
+        visible.sort_by(|(a, _), (b, _)| b.distance.total_cmp(&a.distance));
+
+        for (projection, sprite) in visible {
+            if let Ok((shape, shape_bytes)) = game.cache.get_sprite(sprite) {
+                self.scale_shape(&projection, zbuffer, shape, shape_bytes);
+            }
+        }
+    }
+
+    pub fn draw_weapon(&mut self, game: &Game) {
+        let (weapon_shape, weapon_data) = match game.cache.get_sprite(game.player.weapons.sprite())
+        {
+            Ok(sprite) => sprite,
+            Err(_) => return,
+        };
+
+        self.simple_scale_shape(
+            weapon_shape.left_pix,
+            weapon_shape.right_pix,
+            &weapon_shape.dataofs,
+            weapon_data,
+        );
+    }
+
+    pub fn put_pixel(&mut self, x: u32, y: u32, color_index: usize) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        if color_index >= self.color_map.len() {
+            return;
+        }
+
+        let offset = y as usize * self.width as usize + x as usize;
+
+        if offset < self.buffer.len() {
+            let (r, g, b) = self.color_map[color_index];
+            let (r, g, b) = (r as u32, g as u32, b as u32);
+
+            self.buffer[offset] = (r << 16) | (g << 8) | b;
+        }
+    }
+
+    pub fn put_darkened_pixel(&mut self, x: u32, y: u32, color_index: usize, lightness: u32) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let offset = y as usize * self.width as usize + x as usize;
+
+        if offset >= self.buffer.len() {
+            return;
+        }
+
+        let (r, g, b) = self.color_map[color_index];
+
+        let factor =
+            std::cmp::min(lightness, self.pix_center) as f64 / self.pix_center as f64 / DARKNESS;
+        let r = (r as f64 * factor) as u8 as u32;
+        let g = (g as f64 * factor) as u8 as u32;
+        let b = (b as f64 * factor) as u8 as u32;
+
+        self.buffer[offset] = (r << 16) | (g << 8) | b;
+    }
+
+    /// The framebuffer as packed 8-bit RGB triples, row by row.
+    pub fn rgb(&self) -> Vec<u8> {
+        self.buffer
+            .iter()
+            .flat_map(|pixel| {
+                let [_, r, g, b] = pixel.to_be_bytes();
+                [r, g, b]
+            })
+            .collect()
+    }
+
+    pub fn draw_texture(&mut self, shift_x: u32, shift_y: u32, pic: &Picture) {
+        let mut scj = 0;
+        for y in 0..pic.height {
+            let mut sci = 0;
+            for x in 0..pic.width {
+                let source_index =
+                    (y * (pic.width >> 2) + (x >> 2)) + (x & 3) * (pic.width >> 2) * pic.height;
+                let color = pic.data[source_index as usize];
+                for i in 0..self.scale {
+                    for j in 0..self.scale {
+                        self.put_pixel(sci + j + shift_x, scj + i + shift_y, color as usize);
+                    }
+                }
+
+                sci += self.scale
+            }
+            scj += self.scale
+        }
+    }
+
+    fn scale_shape(
+        &mut self,
+        projection: &ray_caster::Projection,
+        zbuffer: &[f64],
+        shape: &CompShape,
+        shape_bytes: &[u8],
+    ) {
+        let texel_width = projection.width / WALLPIC_WIDTH as f64;
+        let texel_height = 2.0 * projection.height / WALLPIC_WIDTH as f64;
+        let left = projection.column - projection.width / 2.0;
+        let top = self.pix_center as f64 - projection.height;
+        let lightness = projection.height as u32;
+        let read_word = |pos: usize| {
+            shape_bytes
+                .get(pos..pos + 2)
+                .map_or(0, |word| u16::from_le_bytes([word[0], word[1]]))
+        };
+
+        for (i, &cmd) in shape.dataofs.iter().enumerate() {
+            let texel_x = (shape.left_pix as usize + i) as f64;
+            let start_x = (left + texel_x * texel_width).ceil().max(0.0) as u32;
+            let end_x = (left + (texel_x + 1.0) * texel_width)
+                .ceil()
+                .min(self.pix_width as f64);
+
+            for x in start_x..end_x.max(0.0) as u32 {
+                if zbuffer
+                    .get(x as usize)
+                    .is_some_and(|&wall| wall < projection.distance)
+                {
+                    continue;
+                }
+
+                let mut cmd = cmd as usize;
+                loop {
+                    let end_y = read_word(cmd) as usize / 2;
+                    if end_y == 0 {
+                        break;
+                    }
+                    let new_start = read_word(cmd + 2) as i16 as isize;
+                    let start_y = read_word(cmd + 4) as usize / 2;
+
+                    for texel_y in start_y..end_y {
+                        let index = new_start + texel_y as isize;
+                        let color = match shape_bytes.get(index as usize) {
+                            Some(&color) if index >= 0 => color,
+                            _ => continue,
+                        };
+                        let y0 = (top + texel_y as f64 * texel_height).ceil().max(0.0);
+                        let y1 = (top + (texel_y + 1) as f64 * texel_height)
+                            .ceil()
+                            .min(self.pix_height as f64);
+                        for y in y0 as u32..y1.max(0.0) as u32 {
+                            self.put_darkened_pixel(x, y, color as usize, lightness);
+                        }
+                    }
+                    cmd += 6;
+                }
+            }
+        }
+    }
+
+    fn simple_scale_shape(
+        &mut self,
+        left_pix: u16,
+        right_pix: u16,
+        dataofs: &[u16],
+        shape_bytes: &[u8],
+    ) {
+        let sprite_scale_factor = 2;
+        let xcenter = self.pix_width / 2;
+        let height = self.pix_height.min(self.pix_width) + 1;
+
+        let scale = height >> 1;
+        let pixheight = scale * sprite_scale_factor;
+        let actx = xcenter.saturating_sub(scale);
+        let upperedge = self.pix_center.saturating_sub(scale);
+        let mut cmdptr = dataofs.iter();
+
+        let mut i = left_pix;
+        let mut pixcnt = i as u32 * pixheight;
+        let mut rpix = (pixcnt >> 6) + actx;
+
+        while i <= right_pix {
+            let mut lpix = rpix;
+            if lpix >= self.pix_width {
+                break;
+            }
+
+            pixcnt += pixheight;
+            rpix = (pixcnt >> 6) + actx;
+
+            if lpix != rpix && rpix > 0 {
+                if rpix > self.pix_width {
+                    rpix = self.pix_width;
+                    i = right_pix + 1;
+                }
+                let read_word = |line: &mut Iter<u8>| {
+                    u16::from_le_bytes([*line.next().unwrap_or(&0), *line.next().unwrap_or(&0)])
+                };
+                let read_word_signed = |line: &mut Iter<u8>| {
+                    i16::from_le_bytes([*line.next().unwrap_or(&0), *line.next().unwrap_or(&0)])
+                };
+
+                let cline = &shape_bytes[*cmdptr.next().unwrap() as usize..];
+                while lpix < rpix {
+                    let mut line = cline.iter();
+                    let mut endy = read_word(&mut line);
+                    while endy > 0 {
+                        endy >>= 1;
+                        let newstart = read_word_signed(&mut line);
+                        let starty = read_word(&mut line) >> 1;
+                        let mut j = starty;
+                        let mut ycnt = j as u32 * pixheight;
+                        let mut screndy: i32 = (ycnt >> 6) as i32 + upperedge as i32;
+
+                        let mut pixy = screndy as u32;
+                        while j < endy {
+                            let mut scrstarty = screndy;
+                            ycnt += pixheight;
+                            screndy = (ycnt >> 6) as i32 + upperedge as i32;
+                            if scrstarty != screndy && screndy > 0 {
+                                let index = newstart + j as i16;
+                                let col = if index >= 0 {
+                                    shape_bytes[index as usize]
+                                } else {
+                                    0
+                                };
+                                if scrstarty < 0 {
+                                    scrstarty = 0;
+                                }
+                                if screndy > self.pix_height as i32 {
+                                    screndy = self.pix_height as i32;
+                                    j = endy;
+                                }
+
+                                while scrstarty < screndy {
+                                    self.put_pixel(lpix, pixy, col as usize);
+                                    pixy += 1;
+                                    scrstarty += 1;
+                                }
+                            }
+                            j += 1;
+                        }
+                        endy = read_word(&mut line);
+                    }
+                    lpix += 1;
+                }
+            }
+            i += 1;
+        }
+    }
+
+    pub fn draw_fps_counter(&mut self, fps: usize) {
+        let scale = self.hud_scale();
+        self.draw_text(5 * scale, 5 * scale, &fps.to_string(), 255, scale);
+    }
+
+    /// Writes a line of text in a small 3x5 font, each pixel of it `scale`
+    /// pixels wide. Letters are drawn in capitals and characters the font
+    /// lacks are left blank.
+    pub fn draw_text(&mut self, x: u32, y: u32, text: &str, color_index: usize, scale: u32) {
+        for (i, c) in text.chars().enumerate() {
+            let glyph = glyph(c.to_ascii_uppercase());
+            let left = x + i as u32 * GLYPH_ADVANCE * scale;
+            for (dy, row) in glyph.iter().enumerate() {
+                for dx in 0..3 {
+                    if row & (1 << (2 - dx)) == 0 {
+                        continue;
+                    }
+                    for sy in 0..scale {
+                        for sx in 0..scale {
+                            self.put_pixel(
+                                left + dx * scale + sx,
+                                y + dy as u32 * scale + sy,
+                                color_index,
+                            );
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Fills a rectangle, clipped to the framebuffer.
+    pub fn fill_rect(&mut self, x: u32, y: u32, width: u32, height: u32, color_index: usize) {
+        for py in y..(y + height).min(self.height) {
+            for px in x..(x + width).min(self.width) {
+                self.put_pixel(px, py, color_index);
+            }
+        }
+    }
+
+    pub fn draw_minimap(&mut self, map: &Map, player: &Player, minimap_scale: u32) {
+        let map_width = MAP_WIDTH as u32;
+        let map_height = MAP_HEIGHT as u32;
+        let margin = 5 * minimap_scale;
+
+        let minimap_x = self
+            .width
+            .saturating_sub(map_width * minimap_scale + margin);
+        let minimap_y = margin;
+
+        for y in 0..map_height {
+            for x in 0..map_width {
+                let tile = map.tile_at(x as u8, y as u8);
+                let color_index = match tile {
+                    Tile::Wall(_) => 255,
+                    Tile::Floor | Tile::Door { .. } => 0,
+                };
+
+                let screen_x = minimap_x + x * minimap_scale;
+                let screen_y = minimap_y + y * minimap_scale;
+
+                for i in 0..minimap_scale {
+                    for j in 0..minimap_scale {
+                        self.put_pixel(screen_x + i, screen_y + j, color_index);
+                    }
+                }
+            }
+        }
+
+        let player_x = player.x as u32 / MAP_SCALE_W * minimap_scale + minimap_x;
+        let player_y = player.y as u32 / MAP_SCALE_H * minimap_scale + minimap_y;
+
+        let player_color_index = 10;
+
+        for i in 0..minimap_scale {
+            for j in 0..minimap_scale {
+                self.put_pixel(player_x + i, player_y + j, player_color_index);
+            }
+        }
+    }
+}
+
+/// Horizontal distance between the left edges of two characters of text, in
+/// font pixels.
+pub const GLYPH_ADVANCE: u32 = 4;
+/// Vertical distance between two lines of text, in font pixels.
+pub const LINE_HEIGHT: u32 = 7;
+
+// Rows of a 3x5 character, top first, the high bit of each row on the left.
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '_' => [0b000, 0b000, 0b000, 0b000, 0b111],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        ',' => [0b000, 0b000, 0b000, 0b010, 0b100],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        _ => [0; 5],
+    }
+}
+
+/// VSWAP page of the texture for a wall surface. `face` is 0 for the light
+/// texture and 1 for the dark one. Tile 0 has no texture.
+fn wall_page(surface: Surface, door_wall: usize, face: usize) -> Option<usize> {
+    match surface {
+        Surface::Wall(tile) => Some((tile as usize).checked_sub(1)? * 2 + face),
+        Surface::Door { lock, .. } => Some(door_wall + door_page(lock) + face),
+        Surface::DoorSide => Some(door_wall + 2 + face),
+    }
+}
+
+fn door_page(lock: u16) -> usize {
+    match lock {
+        0 => 0,
+        ELEVATOR_LOCK => 4,
+        _ => 6,
+    }
+}
+
+fn build_color_map() -> ColorMap {
+    let palette = [
+        (0, 0, 0),
+        (0, 0, 42),
+        (0, 42, 0),
+        (0, 42, 42),
+        (42, 0, 0),
+        (42, 0, 42),
+        (42, 21, 0),
+        (42, 42, 42),
+        (21, 21, 21),
+        (21, 21, 63),
+        (21, 63, 21),
+        (21, 63, 63),
+        (63, 21, 21),
+        (63, 21, 63),
+        (63, 63, 21),
+        (63, 63, 63),
+        (59, 59, 59),
+        (55, 55, 55),
+        (52, 52, 52),
+        (48, 48, 48),
+        (45, 45, 45),
+        (42, 42, 42),
+        (38, 38, 38),
+        (35, 35, 35),
+        (31, 31, 31),
+        (28, 28, 28),
+        (25, 25, 25),
+        (21, 21, 21),
+        (18, 18, 18),
+        (14, 14, 14),
+        (11, 11, 11),
+        (8, 8, 8),
+        (63, 0, 0),
+        (59, 0, 0),
+        (56, 0, 0),
+        (53, 0, 0),
+        (50, 0, 0),
+        (47, 0, 0),
+        (44, 0, 0),
+        (41, 0, 0),
+        (38, 0, 0),
+        (34, 0, 0),
+        (31, 0, 0),
+        (28, 0, 0),
+        (25, 0, 0),
+        (22, 0, 0),
+        (19, 0, 0),
+        (16, 0, 0),
+        (63, 54, 54),
+        (63, 46, 46),
+        (63, 39, 39),
+        (63, 31, 31),
+        (63, 23, 23),
+        (63, 16, 16),
+        (63, 8, 8),
+        (63, 0, 0),
+        (63, 42, 23),
+        (63, 38, 16),
+        (63, 34, 8),
+        (63, 30, 0),
+        (57, 27, 0),
+        (51, 24, 0),
+        (45, 21, 0),
+        (39, 19, 0),
+        (63, 63, 54),
+        (63, 63, 46),
+        (63, 63, 39),
+        (63, 63, 31),
+        (63, 62, 23),
+        (63, 61, 16),
+        (63, 61, 8),
+        (63, 61, 0),
+        (57, 54, 0),
+        (51, 49, 0),
+        (45, 43, 0),
+        (39, 39, 0),
+        (33, 33, 0),
+        (28, 27, 0),
+        (22, 21, 0),
+        (16, 16, 0),
+        (52, 63, 23),
+        (49, 63, 16),
+        (45, 63, 8),
+        (40, 63, 0),
+        (36, 57, 0),
+        (32, 51, 0),
+        (29, 45, 0),
+        (24, 39, 0),
+        (54, 63, 54),
+        (47, 63, 46),
+        (39, 63, 39),
+        (32, 63, 31),
+        (24, 63, 23),
+        (16, 63, 16),
+        (8, 63, 8),
+        (0, 63, 0),
+        (0, 63, 0),
+        (0, 59, 0),
+        (0, 56, 0),
+        (0, 53, 0),
+        (1, 50, 0),
+        (1, 47, 0),
+        (1, 44, 0),
+        (1, 41, 0),
+        (1, 38, 0),
+        (1, 34, 0),
+        (1, 31, 0),
+        (1, 28, 0),
+        (1, 25, 0),
+        (1, 22, 0),
+        (1, 19, 0),
+        (1, 16, 0),
+        (54, 63, 63),
+        (46, 63, 63),
+        (39, 63, 63),
+        (31, 63, 62),
+        (23, 63, 63),
+        (16, 63, 63),
+        (8, 63, 63),
+        (0, 63, 63),
+        (0, 57, 57),
+        (0, 51, 51),
+        (0, 45, 45),
+        (0, 39, 39),
+        (0, 33, 33),
+        (0, 28, 28),
+        (0, 22, 22),
+        (0, 16, 16),
+        (23, 47, 63),
+        (16, 44, 63),
+        (8, 42, 63),
+        (0, 39, 63),
+        (0, 35, 57),
+        (0, 31, 51),
+        (0, 27, 45),
+        (0, 23, 39),
+        (54, 54, 63),
+        (46, 47, 63),
+        (39, 39, 63),
+        (31, 32, 63),
+        (23, 24, 63),
+        (16, 16, 63),
+        (8, 9, 63),
+        (0, 1, 63),
+        (0, 0, 63),
+        (0, 0, 59),
+        (0, 0, 56),
+        (0, 0, 53),
+        (0, 0, 50),
+        (0, 0, 47),
+        (0, 0, 44),
+        (0, 0, 41),
+        (0, 0, 38),
+        (0, 0, 34),
+        (0, 0, 31),
+        (0, 0, 28),
+        (0, 0, 25),
+        (0, 0, 22),
+        (0, 0, 19),
+        (0, 0, 16),
+        (10, 10, 10),
+        (63, 56, 13),
+        (63, 53, 9),
+        (63, 51, 6),
+        (63, 48, 2),
+        (63, 45, 0),
+        (45, 8, 63),
+        (42, 0, 63),
+        (38, 0, 57),
+        (32, 0, 51),
+        (29, 0, 45),
+        (24, 0, 39),
+        (20, 0, 33),
+        (17, 0, 28),
+        (13, 0, 22),
+        (10, 0, 16),
+        (63, 54, 63),
+        (63, 46, 63),
+        (63, 39, 63),
+        (63, 31, 63),
+        (63, 23, 63),
+        (63, 16, 63),
+        (63, 8, 63),
+        (63, 0, 63),
+        (56, 0, 57),
+        (50, 0, 51),
+        (45, 0, 45),
+        (39, 0, 39),
+        (33, 0, 33),
+        (27, 0, 28),
+        (22, 0, 22),
+        (16, 0, 16),
+        (63, 58, 55),
+        (63, 56, 52),
+        (63, 54, 49),
+        (63, 53, 47),
+        (63, 51, 44),
+        (63, 49, 41),
+        (63, 47, 39),
+        (63, 46, 36),
+        (63, 44, 32),
+        (63, 41, 28),
+        (63, 39, 24),
+        (60, 37, 23),
+        (58, 35, 22),
+        (55, 34, 21),
+        (52, 32, 20),
+        (50, 31, 19),
+        (47, 30, 18),
+        (45, 28, 17),
+        (42, 26, 16),
+        (40, 25, 15),
+        (39, 24, 14),
+        (36, 23, 13),
+        (34, 22, 12),
+        (32, 20, 11),
+        (29, 19, 10),
+        (27, 18, 9),
+        (23, 16, 8),
+        (21, 15, 7),
+        (18, 14, 6),
+        (16, 12, 6),
+        (14, 11, 5),
+        (10, 8, 3),
+        (24, 0, 25),
+        (0, 25, 25),
+        (0, 24, 24),
+        (0, 0, 7),
+        (0, 0, 11),
+        (12, 9, 4),
+        (18, 0, 18),
+        (20, 0, 20),
+        (0, 0, 13),
+        (7, 7, 7),
+        (19, 19, 19),
+        (23, 23, 23),
+        (16, 16, 16),
+        (12, 12, 12),
+        (13, 13, 13),
+        (54, 61, 61),
+        (46, 58, 58),
+        (39, 55, 55),
+        (29, 50, 50),
+        (18, 48, 48),
+        (8, 45, 45),
+        (8, 44, 44),
+        (0, 41, 41),
+        (0, 38, 38),
+        (0, 35, 35),
+        (0, 33, 33),
+        (0, 31, 31),
+        (0, 30, 30),
+        (0, 29, 29),
+        (0, 28, 28),
+        (0, 27, 27),
+        (38, 0, 34),
+    ];
+    palette.map(|(r, g, b)| {
+        (
+            (r * 255 / 63) as u8,
+            (g * 255 / 63) as u8,
+            (b * 255 / 63) as u8,
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ray_caster::Projection;
+
+    #[test]
+    fn sprites_are_hidden_behind_nearer_walls() {
+        let mut renderer = Renderer::new(BASE_WIDTH, BASE_HEIGHT);
+        // A solid 64x64 shape: every column is one post of texels 0..64,
+        // all of color 15, followed by its post list at offset 64.
+        let mut shape_bytes = vec![15; WALLPIC_WIDTH];
+        shape_bytes.extend([128, 0, 0, 0, 0, 0, 0, 0]);
+        let shape = CompShape {
+            left_pix: 0,
+            right_pix: 63,
+            dataofs: vec![WALLPIC_WIDTH as u16; WALLPIC_WIDTH],
+        };
+        let projection = Projection {
+            column: 160.0,
+            width: 64.0,
+            height: 32.0,
+            distance: 2.0,
+        };
+        let mut zbuffer = vec![f64::INFINITY; renderer.pix_width as usize];
+        zbuffer[150..160].fill(1.0);
+
+        renderer.scale_shape(&projection, &zbuffer, &shape, &shape_bytes);
+
+        let pixel = |x: u32| renderer.buffer[(renderer.pix_center * renderer.width + x) as usize];
+        assert_ne!(0, pixel(140));
+        assert_ne!(0, pixel(170));
+        assert!((150..160).all(|x| pixel(x) == 0));
+    }
+
+    #[test]
+    fn doors_pick_their_page_by_lock() {
+        let door = |lock| Surface::Door {
+            lock,
+            position: 0.0,
+        };
+        let door_wall = 98;
+        assert_eq!(Some(98), wall_page(door(0), door_wall, 0));
+        assert_eq!(Some(99), wall_page(door(0), door_wall, 1));
+        for key in 1..=4 {
+            assert_eq!(Some(104), wall_page(door(key), door_wall, 0));
+        }
+        assert_eq!(Some(102), wall_page(door(ELEVATOR_LOCK), door_wall, 0));
+        assert_eq!(Some(100), wall_page(Surface::DoorSide, door_wall, 0));
+        assert_eq!(Some(101), wall_page(Surface::DoorSide, door_wall, 1));
+    }
+
+    #[test]
+    fn wall_tile_zero_has_no_page() {
+        assert_eq!(None, wall_page(Surface::Wall(0), 98, 0));
+        assert_eq!(Some(3), wall_page(Surface::Wall(2), 98, 1));
+    }
+}
@@ -0,0 +1,97 @@
+use crate::renderer::Renderer;
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+/// Writes the renderer's current frame to `path`, as a binary PPM if the
+/// extension is `ppm` and as a PNG otherwise.
+pub fn save(renderer: &Renderer, path: &Path) -> io::Result<()> {
+    let is_ppm = path
+        .extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("ppm"));
+    let rgb = renderer.rgb();
+    if is_ppm {
+        fs::write(path, encode_ppm(renderer.width, renderer.height, &rgb))
+    } else {
+        write_png(path, renderer.width, renderer.height, &rgb)
+    }
+}
+
+/// The first `screenshotNNNN.png` in `dir` that does not exist yet.
+pub fn next_free(dir: &Path) -> PathBuf {
+    (0..)
+        .map(|n| dir.join(format!("screenshot{:04}.png", n)))
+        .find(|path| !path.exists())
+        .unwrap()
+}
+
+fn encode_ppm(width: u32, height: u32, rgb: &[u8]) -> Vec<u8> {
+    let mut data = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    data.extend_from_slice(rgb);
+    data
+}
+
+pub fn write_png(path: &Path, width: u32, height: u32, rgb: &[u8]) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgb)?;
+    Ok(())
+}
+
+/// Writes every frame it is handed to a directory, numbered in sequence.
+pub struct FrameDump {
+    dir: PathBuf,
+    frame: u32,
+}
+
+impl FrameDump {
+    pub fn new(dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(Self {
+            dir: dir.to_owned(),
+            frame: 0,
+        })
+    }
+
+    pub fn write(&mut self, renderer: &Renderer) -> io::Result<()> {
+        let path = self.dir.join(format!("frame{:05}.png", self.frame));
+        self.frame += 1;
+        save(renderer, &path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{BASE_HEIGHT, BASE_WIDTH};
+
+    #[test]
+    fn ppm_has_header_and_pixels() {
+        let data = encode_ppm(2, 1, &[1, 2, 3, 4, 5, 6]);
+        assert_eq!(b"P6\n2 1\n255\n\x01\x02\x03\x04\x05\x06", data.as_slice());
+    }
+
+    #[test]
+    fn png_round_trips() {
+        let mut renderer = Renderer::new(BASE_WIDTH, BASE_HEIGHT);
+        renderer.put_pixel(3, 2, 15);
+        let path = std::env::temp_dir().join(format!(
+            "rust-raycasting-screenshot-{}.png",
+            std::process::id()
+        ));
+        save(&renderer, &path).unwrap();
+
+        let decoder = png::Decoder::new(File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut rgb = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut rgb).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!((renderer.width, renderer.height), reader.info().size());
+        assert_eq!(renderer.rgb(), rgb);
+        let offset = (2 * renderer.width as usize + 3) * 3;
+        assert_eq!([255, 255, 255], rgb[offset..offset + 3]);
+    }
+}
@@ -0,0 +1,204 @@
+use crate::map::{Actor, Boss, EnemyKind};
+use std::f64::consts::PI;
+
+pub const SPR_DEMO: usize = 0;
+pub const SPR_DEATHCAM: usize = 1;
+pub const SPR_STAT_0: usize = 2;
+
+pub const SPR_GRD_S_1: usize = 50;
+pub const SPR_GRD_W1_1: usize = 58;
+pub const SPR_GRD_W2_1: usize = 66;
+pub const SPR_GRD_W3_1: usize = 74;
+pub const SPR_GRD_W4_1: usize = 82;
+pub const SPR_GRD_PAIN_1: usize = 90;
+pub const SPR_GRD_DIE_1: usize = 91;
+pub const SPR_GRD_DIE_2: usize = 92;
+pub const SPR_GRD_DIE_3: usize = 93;
+pub const SPR_GRD_PAIN_2: usize = 94;
+pub const SPR_GRD_DEAD: usize = 95;
+pub const SPR_GRD_SHOOT1: usize = 96;
+pub const SPR_GRD_SHOOT2: usize = 97;
+pub const SPR_GRD_SHOOT3: usize = 98;
+
+pub const SPR_DOG_W1_1: usize = 99;
+pub const SPR_DOG_W2_1: usize = 107;
+pub const SPR_DOG_W3_1: usize = 115;
+pub const SPR_DOG_W4_1: usize = 123;
+pub const SPR_DOG_DIE_1: usize = 131;
+pub const SPR_DOG_DIE_2: usize = 132;
+pub const SPR_DOG_DIE_3: usize = 133;
+pub const SPR_DOG_DEAD: usize = 134;
+pub const SPR_DOG_JUMP1: usize = 135;
+pub const SPR_DOG_JUMP2: usize = 136;
+pub const SPR_DOG_JUMP3: usize = 137;
+
+pub const SPR_SS_S_1: usize = 138;
+pub const SPR_SS_W1_1: usize = 146;
+pub const SPR_SS_W2_1: usize = 154;
+pub const SPR_SS_W3_1: usize = 162;
+pub const SPR_SS_W4_1: usize = 170;
+pub const SPR_SS_PAIN_1: usize = 178;
+pub const SPR_SS_DIE_1: usize = 179;
+pub const SPR_SS_DIE_2: usize = 180;
+pub const SPR_SS_DIE_3: usize = 181;
+pub const SPR_SS_PAIN_2: usize = 182;
+pub const SPR_SS_DEAD: usize = 183;
+pub const SPR_SS_SHOOT1: usize = 184;
+pub const SPR_SS_SHOOT2: usize = 185;
+pub const SPR_SS_SHOOT3: usize = 186;
+
+pub const SPR_MUT_S_1: usize = 187;
+pub const SPR_MUT_W1_1: usize = 195;
+pub const SPR_MUT_W2_1: usize = 203;
+pub const SPR_MUT_W3_1: usize = 211;
+pub const SPR_MUT_W4_1: usize = 219;
+pub const SPR_MUT_PAIN_1: usize = 227;
+pub const SPR_MUT_DIE_1: usize = 228;
+pub const SPR_MUT_DIE_2: usize = 229;
+pub const SPR_MUT_DIE_3: usize = 230;
+pub const SPR_MUT_PAIN_2: usize = 231;
+pub const SPR_MUT_DIE_4: usize = 232;
+pub const SPR_MUT_DEAD: usize = 233;
+pub const SPR_MUT_SHOOT1: usize = 234;
+pub const SPR_MUT_SHOOT2: usize = 235;
+pub const SPR_MUT_SHOOT3: usize = 236;
+pub const SPR_MUT_SHOOT4: usize = 237;
+
+pub const SPR_OFC_S_1: usize = 238;
+pub const SPR_OFC_W1_1: usize = 246;
+pub const SPR_OFC_W2_1: usize = 254;
+pub const SPR_OFC_W3_1: usize = 262;
+pub const SPR_OFC_W4_1: usize = 270;
+pub const SPR_OFC_PAIN_1: usize = 278;
+pub const SPR_OFC_DIE_1: usize = 279;
+pub const SPR_OFC_DIE_2: usize = 280;
+pub const SPR_OFC_DIE_3: usize = 281;
+pub const SPR_OFC_PAIN_2: usize = 282;
+pub const SPR_OFC_DIE_4: usize = 283;
+pub const SPR_OFC_DEAD: usize = 284;
+pub const SPR_OFC_SHOOT1: usize = 285;
+pub const SPR_OFC_SHOOT2: usize = 286;
+pub const SPR_OFC_SHOOT3: usize = 287;
+
+pub const SPR_BLINKY_W1: usize = 288;
+pub const SPR_PINKY_W1: usize = 290;
+pub const SPR_CLYDE_W1: usize = 292;
+pub const SPR_INKY_W1: usize = 294;
+
+pub const SPR_BOSS_W1: usize = 296;
+pub const SPR_BOSS_SHOOT1: usize = 300;
+pub const SPR_BOSS_DEAD: usize = 303;
+pub const SPR_BOSS_DIE1: usize = 304;
+
+pub const SPR_SCHABB_W1: usize = 307;
+pub const SPR_SCHABB_SHOOT1: usize = 311;
+pub const SPR_SCHABB_DIE1: usize = 313;
+pub const SPR_SCHABB_DEAD: usize = 316;
+pub const SPR_HYPO1: usize = 317;
+
+pub const SPR_FAKE_W1: usize = 321;
+pub const SPR_FAKE_SHOOT: usize = 325;
+pub const SPR_FIRE1: usize = 326;
+pub const SPR_FAKE_DIE1: usize = 328;
+pub const SPR_FAKE_DEAD: usize = 333;
+
+pub const SPR_MECHA_W1: usize = 334;
+pub const SPR_MECHA_SHOOT1: usize = 338;
+pub const SPR_MECHA_DEAD: usize = 341;
+pub const SPR_MECHA_DIE1: usize = 342;
+
+pub const SPR_HITLER_W1: usize = 345;
+pub const SPR_HITLER_SHOOT1: usize = 349;
+pub const SPR_HITLER_DEAD: usize = 352;
+pub const SPR_HITLER_DIE1: usize = 353;
+
+pub const SPR_GRETEL_W1: usize = 360;
+pub const SPR_GRETEL_SHOOT1: usize = 364;
+pub const SPR_GRETEL_DEAD: usize = 367;
+pub const SPR_GRETEL_DIE1: usize = 368;
+
+pub const SPR_GIFT_W1: usize = 371;
+pub const SPR_GIFT_SHOOT1: usize = 375;
+pub const SPR_GIFT_DIE1: usize = 377;
+pub const SPR_GIFT_DEAD: usize = 380;
+
+pub const SPR_ROCKET_1: usize = 381;
+pub const SPR_SMOKE_1: usize = 389;
+pub const SPR_BOOM_1: usize = 393;
+
+pub const SPR_FAT_W1: usize = 396;
+pub const SPR_FAT_SHOOT1: usize = 400;
+pub const SPR_FAT_DIE1: usize = 404;
+pub const SPR_FAT_DEAD: usize = 407;
+
+pub const SPR_BJ_W1: usize = 408;
+pub const SPR_BJ_JUMP1: usize = 412;
+
+pub const SPR_KNIFEREADY: usize = 416;
+pub const SPR_PISTOLREADY: usize = 421;
+pub const SPR_MACHINEGUNREADY: usize = 426;
+pub const SPR_CHAINREADY: usize = 431;
+
+// Picks one of the eight rotations of a sprite from the angle the viewer
+// sees the actor at and the angle the actor faces, 0 being the front view.
+pub fn rotation(view_angle: f64, facing: f64) -> usize {
+    let angle = view_angle - PI - facing + PI / 8.0;
+    let angle = angle.rem_euclid(2.0 * PI);
+    (angle / (PI / 4.0)) as usize % 8
+}
+
+pub fn actor_sprite(actor: &Actor, view_angle: f64) -> Option<usize> {
+    match actor {
+        Actor::Static(kind) => Some(kind.sprite()),
+        Actor::DeadGuard => Some(SPR_GRD_DEAD),
+        Actor::Enemy(enemy) => {
+            let (stand, walk) = match enemy.kind {
+                EnemyKind::Guard => (SPR_GRD_S_1, SPR_GRD_W1_1),
+                EnemyKind::Officer => (SPR_OFC_S_1, SPR_OFC_W1_1),
+                EnemyKind::SS => (SPR_SS_S_1, SPR_SS_W1_1),
+                EnemyKind::Dog => (SPR_DOG_W1_1, SPR_DOG_W1_1),
+                EnemyKind::Mutant => (SPR_MUT_S_1, SPR_MUT_W1_1),
+            };
+            let base = if enemy.patrolling { walk } else { stand };
+            Some(base + rotation(view_angle, enemy.direction.angle()))
+        }
+        Actor::Boss(boss) => Some(match boss {
+            Boss::Hans => SPR_BOSS_W1,
+            Boss::Schabbs => SPR_SCHABB_W1,
+            Boss::FakeHitler => SPR_FAKE_W1,
+            Boss::MechaHitler => SPR_MECHA_W1,
+            Boss::Gretel => SPR_GRETEL_W1,
+            Boss::Giftmacher => SPR_GIFT_W1,
+            Boss::Fettgesicht => SPR_FAT_W1,
+            Boss::Blinky => SPR_BLINKY_W1,
+            Boss::Clyde => SPR_CLYDE_W1,
+            Boss::Pinky => SPR_PINKY_W1,
+            Boss::Inky => SPR_INKY_W1,
+        }),
+        Actor::Player(_) | Actor::PushWall | Actor::PathTurn(_) | Actor::EndGame => None,
+    }
+}
+
+/// The corpse left behind by a killed enemy or boss.
+pub fn dead_sprite(actor: &Actor) -> Option<usize> {
+    match actor {
+        Actor::Enemy(enemy) => Some(match enemy.kind {
+            EnemyKind::Guard => SPR_GRD_DEAD,
+            EnemyKind::Officer => SPR_OFC_DEAD,
+            EnemyKind::SS => SPR_SS_DEAD,
+            EnemyKind::Dog => SPR_DOG_DEAD,
+            EnemyKind::Mutant => SPR_MUT_DEAD,
+        }),
+        Actor::Boss(boss) => match boss {
+            Boss::Hans => Some(SPR_BOSS_DEAD),
+            Boss::Schabbs => Some(SPR_SCHABB_DEAD),
+            Boss::FakeHitler => Some(SPR_FAKE_DEAD),
+            Boss::MechaHitler => Some(SPR_MECHA_DEAD),
+            Boss::Gretel => Some(SPR_GRETEL_DEAD),
+            Boss::Giftmacher => Some(SPR_GIFT_DEAD),
+            Boss::Fettgesicht => Some(SPR_FAT_DEAD),
+            Boss::Blinky | Boss::Clyde | Boss::Pinky | Boss::Inky => None,
+        },
+        _ => None,
+    }
+}
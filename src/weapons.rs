@@ -0,0 +1,322 @@
+use crate::constants::*;
+use crate::doors::Doors;
+use crate::enemies::Enemy;
+use crate::map::Map;
+use crate::player::Player;
+use crate::pushwalls::PushWalls;
+use crate::random::Random;
+use crate::ray_caster;
+use crate::sprites::SPR_KNIFEREADY;
+
+pub const MAX_AMMO: u32 = 99;
+const START_AMMO: u32 = 8;
+// Enemies are only hit near the middle of the view, within this much
+// sideways offset per unit of distance, about a tenth of the screen.
+const SHOOT_SLOPE: f64 = 0.15;
+// The knife reaches a tile and a half.
+const KNIFE_RANGE: f64 = 1.5 * MAP_SCALE_W as f64;
+// Each weapon has five sprites in VSWAP, the ready pose and four attack
+// frames.
+const FRAMES_PER_WEAPON: usize = 5;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Weapon {
+    Knife,
+    Pistol,
+    MachineGun,
+    ChainGun,
+}
+
+impl Weapon {
+    pub const ALL: [Weapon; 4] = [
+        Weapon::Knife,
+        Weapon::Pistol,
+        Weapon::MachineGun,
+        Weapon::ChainGun,
+    ];
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Step {
+    Nothing,
+    Fire,
+    Stab,
+    /// Go back a frame while the trigger is held, the machine gun.
+    Repeat,
+    /// Fire and go back a frame while the trigger is held, the chaingun.
+    FireAndRepeat,
+    Done,
+}
+
+// Tics, what happens when they run out and the attack frame to show, the
+// original's attackinfo table.
+const ATTACKS: [[(i32, Step, usize); 4]; 4] = [
+    [
+        (6, Step::Nothing, 1),
+        (6, Step::Stab, 2),
+        (6, Step::Nothing, 3),
+        (6, Step::Done, 4),
+    ],
+    [
+        (6, Step::Nothing, 1),
+        (6, Step::Fire, 2),
+        (6, Step::Nothing, 3),
+        (6, Step::Done, 4),
+    ],
+    [
+        (6, Step::Nothing, 1),
+        (6, Step::Fire, 2),
+        (6, Step::Repeat, 3),
+        (6, Step::Done, 4),
+    ],
+    [
+        (6, Step::Nothing, 1),
+        (6, Step::Fire, 2),
+        (6, Step::FireAndRepeat, 3),
+        (6, Step::Done, 4),
+    ],
+];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Shot {
+    Bullet,
+    Stab,
+}
+
+/// The weapons the player carries and the attack in progress.
+#[derive(Debug, Clone)]
+pub struct Weapons {
+    pub current: Weapon,
+    /// The weapon to go back to when ammo turns up after running dry.
+    pub chosen: Weapon,
+    /// The best weapon picked up, every one below it is owned too.
+    pub best: Weapon,
+    pub ammo: u32,
+    attack: Option<usize>,
+    attack_count: i32,
+    frame: usize,
+    trigger_held: bool,
+}
+
+impl Default for Weapons {
+    fn default() -> Self {
+        Self {
+            current: Weapon::Pistol,
+            chosen: Weapon::Pistol,
+            best: Weapon::Pistol,
+            ammo: START_AMMO,
+            attack: None,
+            attack_count: 0,
+            frame: 0,
+            trigger_held: false,
+        }
+    }
+}
+
+impl Weapons {
+    pub fn is_attacking(&self) -> bool {
+        self.attack.is_some()
+    }
+
+    /// Readies an owned weapon. Not while attacking, and not without ammo,
+    /// the knife is all there is then.
+    pub fn select(&mut self, weapon: Weapon) -> bool {
+        if self.is_attacking() || self.ammo == 0 || weapon > self.best {
+            return false;
+        }
+        self.current = weapon;
+        self.chosen = weapon;
+        true
+    }
+
+    /// Adds ammo, bringing back the chosen gun if only the knife was left.
+    pub fn give_ammo(&mut self, ammo: u32) {
+        if self.ammo == 0 && !self.is_attacking() {
+            self.current = self.chosen;
+        }
+        self.ammo = (self.ammo + ammo).min(MAX_AMMO);
+    }
+
+    /// Picks up a weapon with some ammo, and readies it if it is better
+    /// than any owned so far.
+    pub fn give_weapon(&mut self, weapon: Weapon) {
+        self.give_ammo(6);
+        if weapon > self.best {
+            self.best = weapon;
+            self.current = weapon;
+            self.chosen = weapon;
+        }
+    }
+
+    /// The VSWAP sprite of the weapon in its current frame.
+    pub fn sprite(&self) -> usize {
+        SPR_KNIFEREADY + self.current as usize * FRAMES_PER_WEAPON + self.frame
+    }
+
+    /// Advances the weapon by one tic with the trigger held or not, and
+    /// returns the shot it fired if any. A new attack needs a fresh pull of
+    /// the trigger, the machine gun and chaingun keep firing while it is
+    /// held.
+    pub fn tic(&mut self, fire: bool) -> Option<Shot> {
+        let pulled = fire && !self.trigger_held;
+        self.trigger_held = fire;
+
+        let mut index = match self.attack {
+            Some(index) => index,
+            None => {
+                if pulled {
+                    let (tics, _, frame) = ATTACKS[self.current as usize][0];
+                    self.attack = Some(0);
+                    self.attack_count = tics;
+                    self.frame = frame;
+                }
+                return None;
+            }
+        };
+
+        let attacks = &ATTACKS[self.current as usize];
+        let mut shot = None;
+        self.attack_count -= 1;
+        while self.attack_count <= 0 {
+            let (tics, step, _) = attacks[index];
+            match step {
+                Step::Done => {
+                    if self.ammo == 0 {
+                        self.current = Weapon::Knife;
+                    }
+                    self.attack = None;
+                    self.frame = 0;
+                    return shot;
+                }
+                Step::Fire if self.ammo == 0 => index += 1,
+                Step::FireAndRepeat if self.ammo == 0 => {}
+                Step::Fire | Step::FireAndRepeat => {
+                    if step == Step::FireAndRepeat && fire {
+                        index -= 2;
+                    }
+                    self.ammo -= 1;
+                    shot = Some(Shot::Bullet);
+                }
+                Step::Stab => shot = Some(Shot::Stab),
+                Step::Repeat if self.ammo > 0 && fire => index -= 2,
+                Step::Repeat | Step::Nothing => {}
+            }
+            self.attack_count += tics;
+            index += 1;
+            self.frame = attacks[index].2;
+        }
+        self.attack = Some(index);
+        shot
+    }
+}
+
+/// The nearest enemy that can be shot near the middle of the view with
+/// nothing in between, and how far ahead it is.
+pub fn target(
+    player: &Player,
+    enemies: &[Enemy],
+    map: &Map,
+    doors: &Doors,
+    pushwalls: &PushWalls,
+) -> Option<(usize, f64)> {
+    let (sin, cos) = player.view_angle.sin_cos();
+    let mut candidates: Vec<(usize, f64)> = enemies
+        .iter()
+        .enumerate()
+        .filter(|(_, enemy)| enemy.is_shootable())
+        .filter_map(|(i, enemy)| {
+            let (dx, dy) = (enemy.x - player.x, enemy.y - player.y);
+            let forward = dx * sin + dy * cos;
+            let lateral = dx * cos - dy * sin;
+            (forward > 0.0 && lateral.abs() < forward * SHOOT_SLOPE).then_some((i, forward))
+        })
+        .collect();
+    candidates.sort_by(|(_, a), (_, b)| a.total_cmp(b));
+
+    candidates.into_iter().find(|&(i, _)| {
+        let enemy = (enemies[i].x, enemies[i].y);
+        ray_caster::visible(map, doors, pushwalls, (player.x, player.y), enemy)
+    })
+}
+
+/// Bullet damage by distance in tiles, as in the original: certain and
+/// heavy up close, and further away a growing chance to miss.
+pub fn bullet_damage(player: &Player, enemy: &Enemy, random: &mut Random) -> Option<i32> {
+    let tile = |position: f64, scale: u32| (position / scale as f64) as i32;
+    let dx = (tile(enemy.x, MAP_SCALE_W) - tile(player.x, MAP_SCALE_W)).abs();
+    let dy = (tile(enemy.y, MAP_SCALE_H) - tile(player.y, MAP_SCALE_H)).abs();
+    let distance = dx.max(dy);
+    if distance < 2 {
+        Some(random.byte() / 4)
+    } else if distance < 4 {
+        Some(random.byte() / 6)
+    } else if random.byte() / 12 < distance {
+        None
+    } else {
+        Some(random.byte() / 6)
+    }
+}
+
+/// Knife damage for an enemy `distance` ahead, nothing if out of reach.
+pub fn stab_damage(distance: f64, random: &mut Random) -> Option<i32> {
+    (distance <= KNIFE_RANGE).then(|| random.byte() >> 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tics until each shot of an attack with the trigger held for `held`
+    // tics.
+    fn shots(weapons: &mut Weapons, held: u32, tics: u32) -> Vec<u32> {
+        (0..tics)
+            .filter(|&tic| weapons.tic(tic < held).is_some())
+            .collect()
+    }
+
+    #[test]
+    fn pistol_fires_once_per_pull() {
+        let mut weapons = Weapons::default();
+        assert_eq!(vec![12], shots(&mut weapons, 100, 100));
+        assert_eq!(START_AMMO - 1, weapons.ammo);
+        assert!(!weapons.is_attacking());
+        // Still held from the last attack, then pulled again.
+        assert!(shots(&mut weapons, 1, 100).is_empty());
+        assert_eq!(vec![12], shots(&mut weapons, 1, 100));
+    }
+
+    #[test]
+    fn automatic_weapons_keep_firing_while_held() {
+        let mut weapons = Weapons {
+            best: Weapon::ChainGun,
+            ..Weapons::default()
+        };
+        assert!(weapons.select(Weapon::MachineGun));
+        assert_eq!(vec![12, 24, 36], shots(&mut weapons, 40, 100));
+
+        assert!(weapons.select(Weapon::ChainGun));
+        weapons.ammo = 3;
+        assert_eq!(vec![12, 18, 24], shots(&mut weapons, 100, 100));
+        assert_eq!(0, weapons.ammo);
+        assert_eq!(Weapon::Knife, weapons.current);
+        assert!(!weapons.select(Weapon::Pistol));
+    }
+
+    #[test]
+    fn only_owned_weapons_can_be_selected() {
+        let mut weapons = Weapons::default();
+        assert!(weapons.select(Weapon::Knife));
+        assert_eq!(SPR_KNIFEREADY, weapons.sprite());
+        assert!(!weapons.select(Weapon::MachineGun));
+        weapons.tic(true);
+        assert!(!weapons.select(Weapon::Pistol));
+        assert_eq!(SPR_KNIFEREADY + 1, weapons.sprite());
+    }
+
+    #[test]
+    fn knife_only_reaches_close_enemies() {
+        let mut random = Random::new(1);
+        assert!(stab_damage(KNIFE_RANGE, &mut random).is_some());
+        assert!(stab_damage(KNIFE_RANGE + 1.0, &mut random).is_none());
+    }
+}
@@ -0,0 +1,261 @@
+use crate::audio;
+use crate::bindings::{Action, Bindings};
+use crate::cache;
+use crate::clock::Clock;
+use crate::constants::TICRATE;
+use crate::game::Game;
+use crate::input::{self, Capture, Controller, Mouse, Rebinding};
+use crate::renderer::{Renderer, GLYPH_ADVANCE, LINE_HEIGHT};
+use crate::screenshot::{self, FrameDump};
+use crate::Opts;
+use minifb::{KeyRepeat, ScaleMode, Window, WindowOptions};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+const TITLE: &str = "Rust Raycasting";
+
+/// Opens the game window and runs the game in it until it is closed.
+pub fn run(args: &Opts, mut game: Game, mut renderer: Renderer, mut dump: Option<FrameDump>) {
+    let bindings = match Bindings::load(&args.controls) {
+        Ok(bindings) => bindings,
+        Err(err) => {
+            eprintln!("Could not load controls: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    let mut window = match Window::new(
+        TITLE,
+        renderer.width as usize,
+        renderer.height as usize,
+        WindowOptions {
+            borderless: args.borderless,
+            title: !args.borderless,
+            topmost: args.borderless,
+            resize: true,
+            scale_mode: ScaleMode::UpperLeft,
+            ..WindowOptions::default()
+        },
+    ) {
+        Ok(window) => window,
+        Err(err) => {
+            eprintln!("Could not open a window: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    if args.borderless {
+        window.set_position(0, 0);
+    }
+    window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
+
+    if !show_title(&game, &mut renderer, &mut window) {
+        return;
+    }
+
+    let mut last_time = Instant::now();
+    let mut frame_count = 0;
+    let mut fps = 0;
+
+    let _music = audio::play_music(&args.data_dir);
+    let mut clock = Clock::new(TICRATE, Instant::now());
+
+    let mouse = (!args.no_mouse)
+        .then(|| Mouse::new(args.mouse_sensitivity, args.invert_mouse).warping(&window));
+    let mut controller = Controller::new(bindings, mouse);
+    let mut paused = false;
+    // Mouse turning is applied on the next tic, however many frames pass first.
+    let mut mouse_turn = 0.0;
+
+    loop {
+        if controller.is_rebinding() {
+            if !window.is_open() {
+                break;
+            }
+            fit_to_window(&mut renderer, &window);
+            renderer.render(&game);
+            rebind(&mut controller, &mut window, &mut renderer, &args.controls);
+            clock.advance(Instant::now());
+            present(&renderer, &mut window);
+            continue;
+        }
+
+        let controls = match input::process_input(&mut window, &mut controller) {
+            Ok(controls) => controls,
+            Err(_) => break,
+        };
+        if controls.pressed(Action::Pause) {
+            paused = !paused;
+        }
+        if controls.pressed(Action::Map) {
+            renderer.show_minimap = !renderer.show_minimap;
+        }
+        if controls.pressed(Action::Rebind) {
+            controller.start_rebinding();
+        }
+
+        let tics = clock.advance(Instant::now());
+        if !paused {
+            controls.operate(&mut game);
+            mouse_turn += controls.mouse_turn;
+            for _ in 0..tics {
+                controls.tic(&mut game, mouse_turn);
+                mouse_turn = 0.0;
+            }
+        }
+        fit_to_window(&mut renderer, &window);
+
+        let now = Instant::now();
+        frame_count += 1;
+
+        if now.duration_since(last_time) >= Duration::from_secs(1) {
+            fps = frame_count;
+            frame_count = 0;
+            last_time = now;
+        }
+
+        renderer.render(&game);
+        if game.is_over() {
+            draw_game_over(&mut renderer);
+        }
+        renderer.draw_fps_counter(fps);
+        present(&renderer, &mut window);
+
+        if controls.pressed(Action::Screenshot) {
+            let path = screenshot::next_free(Path::new("."));
+            match screenshot::save(&renderer, &path) {
+                Ok(()) => println!("Saved {}", path.display()),
+                Err(err) => eprintln!("Could not write {}: {}", path.display(), err),
+            }
+        }
+        if let Some(frames) = &mut dump {
+            if let Err(err) = frames.write(&renderer) {
+                eprintln!("Stopped dumping frames: {}", err);
+                dump = None;
+            }
+        }
+    }
+}
+
+// Runs the controls menu for a frame and draws it over the view, saving the
+// bindings once it is closed.
+fn rebind(controller: &mut Controller, window: &mut Window, renderer: &mut Renderer, file: &Path) {
+    let state = controller.rebind(window);
+    if state == Rebinding::Done {
+        match controller.bindings.save(file) {
+            Ok(()) => println!("Saved controls to {}", file.display()),
+            Err(err) => eprintln!("Could not save controls: {}", err),
+        }
+        return;
+    }
+    draw_controls(renderer, &controller.bindings, state);
+}
+
+// Lists every action with its bindings on a dark box, the selected one
+// marked, with the keys to use below.
+fn draw_controls(renderer: &mut Renderer, bindings: &Bindings, state: Rebinding) {
+    let selected = match state {
+        Rebinding::Choosing(action) | Rebinding::Waiting(action, _) => action,
+        Rebinding::Done => return,
+    };
+    let mut lines = vec![String::from("CONTROLS"), String::new()];
+    for action in Action::ALL {
+        let inputs: Vec<String> = bindings
+            .get(action)
+            .iter()
+            .map(|input| input.name())
+            .collect();
+        let marker = if action == selected { ">" } else { " " };
+        lines.push(format!(
+            "{} {:<13}{}",
+            marker,
+            action.name(),
+            inputs.join(", ")
+        ));
+    }
+    lines.push(String::new());
+    lines.push(match state {
+        Rebinding::Waiting(action, Capture::Replace) => {
+            format!("PRESS THE NEW KEY FOR {}, ESCAPE: CANCEL", action.name())
+        }
+        Rebinding::Waiting(action, Capture::Add) => {
+            format!("PRESS A KEY TO ADD TO {}, ESCAPE: CANCEL", action.name())
+        }
+        _ => String::from("UP/DOWN: PICK  ENTER: REPLACE  SPACE: ADD"),
+    });
+    lines.push(String::from("BACKSPACE: DEFAULTS  ESCAPE: SAVE AND CLOSE"));
+
+    let scale = renderer.hud_scale();
+    let margin = 4 * scale;
+    let columns = lines.iter().map(|line| line.len()).max().unwrap_or(0) as u32;
+    renderer.fill_rect(
+        margin,
+        margin,
+        columns * GLYPH_ADVANCE * scale + 2 * margin,
+        lines.len() as u32 * LINE_HEIGHT * scale + 2 * margin,
+        0,
+    );
+    for (i, line) in lines.iter().enumerate() {
+        let y = 2 * margin + i as u32 * LINE_HEIGHT * scale;
+        renderer.draw_text(2 * margin, y, line, 15, scale);
+    }
+}
+
+// Writes GAME OVER in the middle of the view.
+fn draw_game_over(renderer: &mut Renderer) {
+    const TEXT: &str = "GAME OVER";
+    let scale = 2 * renderer.hud_scale();
+    let width = TEXT.len() as u32 * GLYPH_ADVANCE * scale;
+    let x = renderer.width.saturating_sub(width) / 2;
+    let y = renderer.height.saturating_sub(LINE_HEIGHT * scale) / 2;
+    renderer.draw_text(x, y, TEXT, 15, scale);
+}
+
+// Follows the window size after the user resized it, unless it is minimized.
+// Returns whether the framebuffer was reallocated.
+fn fit_to_window(renderer: &mut Renderer, window: &Window) -> bool {
+    let (width, height) = window.get_size();
+    let (width, height) = (width as u32, height as u32);
+    let resized = width > 0 && height > 0 && (width, height) != (renderer.width, renderer.height);
+    if resized {
+        renderer.resize(width, height);
+    }
+    resized
+}
+
+fn present(renderer: &Renderer, window: &mut Window) {
+    window
+        .update_with_buffer(
+            &renderer.buffer,
+            renderer.width as usize,
+            renderer.height as usize,
+        )
+        .unwrap();
+}
+
+// Shows the title screen until a key is pressed. Returns false if the window
+// was closed instead.
+fn show_title(game: &Game, renderer: &mut Renderer, window: &mut Window) -> bool {
+    draw_title(game, renderer);
+    while window.get_keys_pressed(KeyRepeat::No).is_empty() && window.is_open() {
+        if fit_to_window(renderer, window) {
+            draw_title(game, renderer);
+        }
+        present(renderer, window);
+    }
+    window.is_open()
+}
+
+fn draw_title(game: &Game, renderer: &mut Renderer) {
+    if let Ok(titlepic) = game.cache.get_pic(cache::TITLEPIC) {
+        let x = renderer
+            .width
+            .saturating_sub(titlepic.width * renderer.scale)
+            / 2;
+        let y = renderer
+            .height
+            .saturating_sub(titlepic.height * renderer.scale)
+            / 2;
+        renderer.draw_texture(x, y, titlepic);
+    }
+}